@@ -0,0 +1,265 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError, Weak},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::error;
+use rand::seq::SliceRandom;
+use tokio::sync::RwLock;
+
+use crate::{
+    audio_player::{Channel, Player},
+    background::next_background,
+    event::{Event, Events, NowPlaying},
+    func::{list_items, load_sound_lists},
+    playlist::PlaylistEntries,
+    randomizer::sound_candidates,
+    rotation::Rotation,
+    track_info::TrackInfo,
+    Config, Result,
+};
+
+/// Time between idle checks
+const TICK: Duration = Duration::from_millis(250);
+/// Time between ambient wheel steps
+const WHEEL_STEP: Duration = Duration::from_millis(1500);
+/// Time between background changes
+const BACKGROUND_INTERVAL: Duration = Duration::from_secs(20);
+
+/// Config values the attract mode depends on
+#[derive(Clone, Copy)]
+struct Settings {
+    /// Idle time before the attract mode starts, zero disables it
+    delay: Duration,
+    /// Idle music fade-in and fade-out time in milliseconds
+    fade: u32,
+    music: bool,
+}
+
+impl From<&Config> for Settings {
+    fn from(config: &Config) -> Self {
+        Self {
+            delay: Duration::from_secs(config.attract_delay().into()),
+            fade: config.music_fade_out(),
+            music: config.music(),
+        }
+    }
+}
+
+struct AttractState {
+    active: bool,
+    /// Wheel entries shown by ambient steps, shuffled when the attract mode starts
+    items: Vec<String>,
+    last_activity: Instant,
+    list_name: String,
+    next_background: Instant,
+    next_step: Instant,
+    position: usize,
+    rolling: bool,
+    settings: Settings,
+}
+
+/// Attract mode that runs while the app is idle: plays idle music from `data/sounds/idle`,
+/// cycles backgrounds and slowly moves the wheel through the current list
+pub(crate) struct Attract {
+    events: Events,
+    player: Weak<RwLock<Player>>,
+    state: Arc<Mutex<AttractState>>,
+}
+
+impl Attract {
+    /// Starts the idle timer thread, it stops when the player is dropped
+    pub fn spawn(
+        player: &Arc<RwLock<Player>>,
+        events: Events,
+        rotation: Arc<Mutex<Rotation>>,
+        data_path: &Path,
+        config: &Config,
+    ) -> Self {
+        let now = Instant::now();
+        let state = Arc::new(Mutex::new(AttractState {
+            active: false,
+            items: vec![],
+            last_activity: now,
+            list_name: String::new(),
+            next_background: now,
+            next_step: now,
+            position: 0,
+            rolling: false,
+            settings: config.into(),
+        }));
+        let mut playlist_entries = PlaylistEntries::new();
+        let task = AttractTask {
+            data_path: data_path.to_path_buf(),
+            events: events.clone(),
+            idle_sounds: load_sound_lists(data_path, "idle", &mut playlist_entries),
+            player: Arc::downgrade(player),
+            playlist_entries,
+            rotation,
+            state: state.clone(),
+        };
+        thread::spawn(move || task.run());
+
+        Self {
+            events,
+            player: Arc::downgrade(player),
+            state,
+        }
+    }
+
+    pub fn set_config(&self, config: &Config) {
+        self.lock().settings = config.into();
+    }
+
+    /// Restarts the idle timer and stops the attract mode, fading out the idle music
+    pub fn wake(&self, list_name: &str) {
+        let mut state = self.lock();
+        state.last_activity = Instant::now();
+        state.list_name = list_name.to_string();
+        if !state.active {
+            return;
+        }
+
+        self.stop(&mut state);
+        if let Some(player) = self.player.upgrade() {
+            if let Err(e) = player
+                .blocking_read()
+                .fade_out(Channel::Music, state.settings.fade)
+            {
+                error!("{:#?}", e);
+            }
+        }
+    }
+
+    /// Pauses the attract mode for the roll, the roll takes over the music. The idle timer
+    /// starts over when the roll ends
+    pub fn set_rolling(&self, list_name: &str, rolling: bool) {
+        let mut state = self.lock();
+        state.last_activity = Instant::now();
+        state.list_name = list_name.to_string();
+        state.rolling = rolling;
+        if rolling && state.active {
+            self.stop(&mut state);
+        }
+    }
+
+    fn stop(&self, state: &mut AttractState) {
+        state.active = false;
+        self.events.emit(Event::Attract(false));
+    }
+
+    fn lock(&self) -> MutexGuard<'_, AttractState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+struct AttractTask {
+    data_path: PathBuf,
+    events: Events,
+    idle_sounds: HashMap<String, Vec<PathBuf>>,
+    player: Weak<RwLock<Player>>,
+    playlist_entries: PlaylistEntries,
+    /// Background rotation of the randomizer
+    rotation: Arc<Mutex<Rotation>>,
+    state: Arc<Mutex<AttractState>>,
+}
+
+impl AttractTask {
+    fn run(self) {
+        loop {
+            thread::sleep(TICK);
+
+            let player = match self.player.upgrade() {
+                Some(player) => player,
+                None => break,
+            };
+
+            // State stays locked during the step, so a roll can't start in the middle of it
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Err(e) = self.step(&mut state, &player) {
+                error!("{:#?}", e);
+            }
+        }
+    }
+
+    fn step(&self, state: &mut AttractState, player: &RwLock<Player>) -> Result<()> {
+        let now = Instant::now();
+        if !state.active {
+            if state.rolling
+                || state.settings.delay.is_zero()
+                || now.duration_since(state.last_activity) < state.settings.delay
+            {
+                return Ok(());
+            }
+
+            self.start(state, player)?;
+        }
+
+        if now >= state.next_step {
+            state.next_step = now + WHEEL_STEP;
+            let total = state.items.len();
+            if total > 0 {
+                state.position = (state.position + 1) % total;
+                let position = state.position + total;
+                self.events.emit(Event::WheelList(
+                    (position - 2..position + 3)
+                        .map(|x| state.items[x % total].clone())
+                        .collect(),
+                ));
+            }
+        }
+
+        if now >= state.next_background {
+            state.next_background = now + BACKGROUND_INTERVAL;
+            self.events.emit(Event::Background(next_background(
+                &self.data_path,
+                &self.rotation,
+                &state.list_name,
+            )?));
+        }
+
+        Ok(())
+    }
+
+    fn start(&self, state: &mut AttractState, player: &RwLock<Player>) -> Result<()> {
+        let now = Instant::now();
+        state.active = true;
+        state.next_background = now;
+        state.next_step = now;
+        state.position = 0;
+
+        // The wheel stays empty if the list can't be read, music and backgrounds still run
+        state.items = list_items(&self.data_path, &state.list_name).unwrap_or_default();
+        state.items.shuffle(&mut rand::thread_rng());
+
+        self.events.emit(Event::Attract(true));
+
+        if !state.settings.music {
+            return Ok(());
+        }
+
+        let sound_path =
+            match sound_candidates(&self.idle_sounds, &state.list_name, &HashSet::new())
+                .and_then(|(_, sounds)| sounds.choose(&mut rand::thread_rng()).cloned())
+            {
+                Some(sound_path) => sound_path,
+                None => return Ok(()),
+            };
+
+        let mut player = player.blocking_write();
+        player.stop_all();
+        let loop_section = TrackInfo::load(&sound_path).loop_section();
+        let track = player.create_stream(&sound_path, Some(loop_section))?;
+        player.play(Channel::Music, track, state.settings.fade)?;
+        self.events.emit(NowPlaying::event(
+            "idle",
+            &sound_path,
+            &self.playlist_entries,
+        ));
+
+        Ok(())
+    }
+}
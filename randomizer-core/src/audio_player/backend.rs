@@ -0,0 +1,121 @@
+use std::{path::Path, sync::Arc, thread, time::Duration};
+
+use crate::Result;
+
+pub type SyncCallback = Box<dyn Fn() + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncType {
+    /// Stream has reached its end
+    End,
+    /// Volume slide has finished
+    Slide,
+}
+
+/// Repeated part of a stream. The stream plays from its start up to `end` once, then repeats
+/// from `start` to `end`, so an intro is heard only once
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LoopSection {
+    /// Loop start in seconds
+    pub start: f64,
+    /// Loop end in seconds, `None` is the end of the file
+    pub end: Option<f64>,
+}
+
+impl LoopSection {
+    /// Returns `true` if the section is the whole file
+    pub fn is_whole(&self) -> bool {
+        self.start <= 0.0 && self.end.is_none()
+    }
+}
+
+/// Audio output used by the player, implemented on top of an audio library
+pub trait AudioBackend: Send + Sync {
+    /// Returns list of enabled devices as (driver, name) pairs
+    fn list_devices(&self) -> Result<Vec<(String, String)>>;
+
+    /// Switches output to the device with the given driver, empty string means the default device
+    fn set_device(&self, driver: &str) -> Result<()>;
+
+    /// Sets global volume, `volume` is in 0-100 range
+    fn set_volume(&self, volume: u32) -> Result<()>;
+
+    /// Creates stream of the file, it repeats `loop_section` if it's set
+    fn create_stream(
+        &self,
+        path: &Path,
+        loop_section: Option<LoopSection>,
+    ) -> Result<Box<dyn AudioStream>>;
+
+    /// Creates stream that plays file contents loaded into memory, `path` is the file they were
+    /// read from
+    fn create_stream_from_memory(
+        &self,
+        path: &Path,
+        data: Arc<[u8]>,
+        loop_section: Option<LoopSection>,
+    ) -> Result<Box<dyn AudioStream>>;
+
+    /// Decodes short sound into memory, so it can be played many times without file I/O
+    fn load_sample(&self, path: &Path) -> Result<Box<dyn AudioSample>>;
+
+    /// Opens the file for decoding without playback, e.g. for analysis
+    fn decode(&self, path: &Path) -> Result<Box<dyn AudioDecoder>>;
+}
+
+/// Source of decoded samples, interleaved by channel
+pub trait AudioDecoder: Send {
+    fn channels(&self) -> u16;
+
+    fn sample_rate(&self) -> u32;
+
+    /// Fills `buffer` with samples in -1..1 range, returns number of samples written, zero at the
+    /// end of the file
+    fn read(&mut self, buffer: &mut [f32]) -> Result<usize>;
+}
+
+pub trait AudioSample: Send + Sync {
+    /// Plays the sample once over its previous playbacks, `volume` is in 0-1 range, `speed`
+    /// changes both playback rate and pitch
+    fn play(&self, volume: f32, speed: f32) -> Result<()>;
+}
+
+pub trait AudioStream: Send + Sync {
+    fn play(&self, restart: bool) -> Result<()>;
+
+    /// Sets volume of the stream, `volume` is in 0-1 range
+    fn set_volume(&self, volume: f32) -> Result<()>;
+
+    /// Returns playback position in seconds
+    fn position(&self) -> Result<f64>;
+
+    /// Moves playback to `position` seconds, meant to be called before the stream plays
+    fn seek(&self, position: f64) -> Result<()>;
+
+    /// Returns FFT magnitudes of the audio played last, in linear frequency bins from 0 Hz to half
+    /// the sample rate. Empty if the backend can't analyse playback
+    fn fft(&self) -> Result<Vec<f32>>;
+
+    /// Changes playback rate together with pitch, 1.0 is the original rate
+    fn set_speed(&self, speed: f32) -> Result<()>;
+
+    /// Slides volume to `volume` over `time` milliseconds, negative volume stops the stream after the slide
+    fn slide_volume(&self, volume: f32, time: u32) -> Result<()>;
+
+    /// Registers one-time callback, it is called from the audio thread
+    fn set_sync(&mut self, sync_type: SyncType, callback: SyncCallback) -> Result<()>;
+}
+
+/// Calls sync callbacks from a separate thread after `delay`, so they can lock the player
+pub(super) fn spawn_callbacks(callbacks: Vec<SyncCallback>, delay: Duration) {
+    if callbacks.is_empty() {
+        return;
+    }
+
+    thread::spawn(move || {
+        thread::sleep(delay);
+        for callback in callbacks {
+            callback();
+        }
+    });
+}
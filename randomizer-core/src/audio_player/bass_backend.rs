@@ -0,0 +1,310 @@
+use std::{
+    env::{
+        self,
+        consts::{DLL_EXTENSION, DLL_PREFIX, DLL_SUFFIX},
+    },
+    ffi::{c_void, CStr, CString},
+    ops::Drop,
+    path::Path,
+    ptr::null_mut,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use bass_sys::{
+    BASS_ChannelSetDevice, BASS_Free, BASS_GetDevice, BASS_GetDeviceInfo, BASS_Init,
+    BASS_PluginFree, BASS_PluginLoad, BASS_SetConfig, BASS_SetDevice, BassDeviceInfo,
+    BASS_CONFIG_GVOL_SAMPLE, BASS_CONFIG_GVOL_STREAM, BASS_CONFIG_UNICODE, BASS_DEVICE_DEFAULT,
+    BASS_DEVICE_ENABLED, DWORD,
+};
+use libloading::Library;
+use log::{error, warn};
+use once_cell::sync::OnceCell;
+use walkdir::WalkDir;
+
+use crate::{Error, Result};
+
+use super::{
+    backend::{AudioBackend, AudioDecoder, AudioSample, AudioStream, LoopSection},
+    error::get_bass_error,
+    BassDecoder, BassSample, BassStream,
+};
+
+/// First real output device, used when none is marked as the default one
+const FIRST_DEVICE: u32 = 1;
+
+/// Handles of live streams and samples, they are moved along when the output device changes.
+/// BASS state is global, so the list is too
+static CHANNELS: Mutex<Vec<DWORD>> = Mutex::new(vec![]);
+
+/// BASS library loaded by `check_library`, kept so it's loaded only once
+static LIBRARY: OnceCell<Library> = OnceCell::new();
+
+pub(super) fn register_channel(handle: DWORD) {
+    CHANNELS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(handle);
+}
+
+pub(super) fn unregister_channel(handle: DWORD) {
+    CHANNELS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .retain(|x| *x != handle);
+}
+
+/// Audio backend built on the BASS library
+pub struct BassBackend;
+
+impl BassBackend {
+    pub fn init(plugins_dir: impl AsRef<Path>, device: &str, volume: u32) -> Result<Self> {
+        BassBackend::check_library()?;
+        if BASS_SetConfig(BASS_CONFIG_UNICODE, 1) == 0 {
+            get_bass_error("Failed to enable Unicode for device information")?;
+        }
+        BassBackend::load_plugins(plugins_dir)?;
+        if let Err(e) = BassBackend::device_index_or_default(device)
+            .and_then(|device| BassBackend::init_device(device as i32))
+        {
+            // Plugins are loaded again on the next attempt
+            BASS_PluginFree(0);
+            return Err(e);
+        }
+
+        let result = Self;
+        result.set_volume(volume)?;
+
+        Ok(result)
+    }
+
+    /// Returns list of enabled devices as (driver, name) pairs, works without initialized device
+    pub fn devices() -> Result<Vec<(String, String)>> {
+        BassBackend::check_library()?;
+
+        let mut result = vec![];
+
+        let mut i = 1;
+        let mut device_info: BassDeviceInfo = BassDeviceInfo::new(null_mut(), null_mut(), 0);
+        loop {
+            if BASS_GetDeviceInfo(i, &mut device_info) == 0 {
+                break;
+            }
+
+            if device_info.flags & BASS_DEVICE_ENABLED != 0 {
+                result.push((
+                    unsafe { CStr::from_ptr(device_info.driver.cast()) }
+                        .to_str()?
+                        .to_string(),
+                    unsafe { CStr::from_ptr(device_info.name.cast()) }
+                        .to_str()?
+                        .to_string(),
+                ));
+            }
+
+            i += 1;
+        }
+
+        Ok(result)
+    }
+
+    /// bass-sys panics on every call when it can't load the library, so the library is loaded
+    /// here first from the same place, next to the executable
+    fn check_library() -> Result<()> {
+        LIBRARY
+            .get_or_try_init(|| {
+                let mut path = env::current_exe()?;
+                path.set_file_name(format!("{DLL_PREFIX}bass{DLL_SUFFIX}"));
+                if !path.is_file() {
+                    return Err(format!("{} not found", path.display()).into());
+                }
+                unsafe { Library::new(path) }.map_err(Box::<dyn std::error::Error>::from)
+            })
+            .map(|_| ())
+            .map_err(|e| {
+                error!("Failed to load BASS library: {:#?}", e);
+                Error::BassUnavailable
+            })
+    }
+
+    /// Returns index of the enabled device with the given driver, `None` if it's missing.
+    /// Unplugged devices stay in the list but are disabled
+    fn find_device_index(driver: &str) -> Result<Option<u32>> {
+        let mut i = 1;
+        let mut device_info = BassDeviceInfo::new(null_mut::<c_void>(), null_mut::<c_void>(), 0);
+
+        while BASS_GetDeviceInfo(i, &mut device_info) != 0 {
+            if !device_info.driver.is_null() && device_info.flags & BASS_DEVICE_ENABLED != 0 {
+                let c_str: &CStr = unsafe { CStr::from_ptr(device_info.driver.cast()) };
+                if c_str.to_str()? == driver {
+                    return Ok(Some(i));
+                }
+            }
+
+            i += 1;
+        }
+
+        Ok(None)
+    }
+
+    /// Returns index of the system default device
+    fn default_device_index() -> u32 {
+        let mut i = 1;
+        let mut device_info = BassDeviceInfo::new(null_mut::<c_void>(), null_mut::<c_void>(), 0);
+
+        while BASS_GetDeviceInfo(i, &mut device_info) != 0 {
+            let flags = BASS_DEVICE_ENABLED | BASS_DEVICE_DEFAULT;
+            if device_info.flags & flags == flags {
+                return i;
+            }
+
+            i += 1;
+        }
+
+        FIRST_DEVICE
+    }
+
+    /// Returns index of the device with the given driver, or of the default device if the driver
+    /// is empty or missing
+    fn device_index_or_default(driver: &str) -> Result<u32> {
+        if driver.is_empty() {
+            return Ok(BassBackend::default_device_index());
+        }
+
+        Ok(match BassBackend::find_device_index(driver)? {
+            Some(device) => device,
+            None => {
+                warn!("Audio device {driver} is not available, using the default one");
+                BassBackend::default_device_index()
+            }
+        })
+    }
+
+    fn init_device(device: i32) -> Result<()> {
+        if BASS_Init(device, 44100, 0, null_mut::<c_void>(), null_mut::<c_void>()) == 0 {
+            get_bass_error("Failed to initialize audio device")?;
+        }
+
+        Ok(())
+    }
+
+    // Baseline code from before `is_some_and`
+    #[allow(clippy::unnecessary_map_or)]
+    fn load_plugins(plugins_dir: impl AsRef<Path>) -> Result<()> {
+        for file_path in WalkDir::new(plugins_dir)
+            .follow_links(false)
+            .max_depth(1)
+            .into_iter()
+            .filter_map(std::result::Result::ok)
+            .filter(|file| {
+                file.path()
+                    .extension()
+                    .map_or(false, |ext| ext == DLL_EXTENSION)
+            })
+        {
+            let c_path = CString::new(file_path.path().to_string_lossy().as_ref())?;
+            let handle = BASS_PluginLoad(c_path.as_ptr().cast::<c_void>(), 0);
+            if handle == 0 {
+                get_bass_error("Failed to load BASS plugin")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn free(&mut self) -> Result<()> {
+        if BASS_PluginFree(0) == 0 {
+            get_bass_error("Failed to free plugins")?;
+        }
+
+        if BASS_Free() == 0 {
+            get_bass_error("Failed to free BASS")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl AudioBackend for BassBackend {
+    fn list_devices(&self) -> Result<Vec<(String, String)>> {
+        BassBackend::devices()
+    }
+
+    fn set_device(&self, driver: &str) -> Result<()> {
+        let device = BassBackend::device_index_or_default(driver)?;
+        let old_device = BASS_GetDevice();
+
+        if device == old_device {
+            return Ok(());
+        }
+
+        BassBackend::init_device(device as i32)?;
+
+        // Playing streams continue on the new device instead of being freed with the old one
+        for handle in CHANNELS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+        {
+            if BASS_ChannelSetDevice(*handle, device) == 0 {
+                if let Err(e) = get_bass_error("Failed to move channel to the new device") {
+                    error!("{:#?}", e);
+                }
+            }
+        }
+
+        if BASS_SetDevice(old_device) == 0 {
+            get_bass_error("Failed to set default device")?;
+        }
+        if BASS_Free() == 0 {
+            get_bass_error("Failed to free device")?;
+        }
+        if BASS_SetDevice(device) == 0 {
+            get_bass_error("Failed to set default device")?;
+        }
+
+        Ok(())
+    }
+
+    fn set_volume(&self, volume: u32) -> Result<()> {
+        // Ticks are samples, they have a global volume of their own
+        for option in [BASS_CONFIG_GVOL_STREAM, BASS_CONFIG_GVOL_SAMPLE] {
+            if BASS_SetConfig(option, volume.min(100) * 100) == 0 {
+                get_bass_error("Failed to set volume")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn create_stream(
+        &self,
+        path: &Path,
+        loop_section: Option<LoopSection>,
+    ) -> Result<Box<dyn AudioStream>> {
+        Ok(Box::new(BassStream::from_file(path, loop_section)?))
+    }
+
+    fn create_stream_from_memory(
+        &self,
+        _path: &Path,
+        data: Arc<[u8]>,
+        loop_section: Option<LoopSection>,
+    ) -> Result<Box<dyn AudioStream>> {
+        Ok(Box::new(BassStream::from_memory(data, loop_section)?))
+    }
+
+    fn load_sample(&self, path: &Path) -> Result<Box<dyn AudioSample>> {
+        Ok(Box::new(BassSample::from_file(path)?))
+    }
+
+    fn decode(&self, path: &Path) -> Result<Box<dyn AudioDecoder>> {
+        Ok(Box::new(BassDecoder::from_file(path)?))
+    }
+}
+
+impl Drop for BassBackend {
+    fn drop(&mut self) {
+        if let Err(e) = self.free() {
+            error!("{:#?}", e);
+        }
+    }
+}
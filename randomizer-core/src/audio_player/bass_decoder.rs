@@ -0,0 +1,101 @@
+use std::{
+    ffi::c_void,
+    mem::{size_of, size_of_val},
+    path::Path,
+    ptr::null,
+};
+
+use bass_sys::{
+    BASS_ChannelGetData, BASS_ChannelGetInfo, BASS_ErrorGetCode, BASS_StreamCreateFile,
+    BASS_StreamFree, BassChannelInfo, BASS_DATA_FLOAT, BASS_ERROR_ENDED, BASS_SAMPLE_FLOAT,
+    BASS_STREAM_DECODE, BASS_UNICODE, HSTREAM,
+};
+use log::error;
+use widestring::U16CString;
+
+use crate::Result;
+
+use super::{backend::AudioDecoder, error::get_bass_error};
+
+pub struct BassDecoder {
+    channels: u16,
+    handle: HSTREAM,
+    sample_rate: u32,
+}
+
+impl BassDecoder {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let file = U16CString::from_str(path.to_string_lossy())?;
+        let handle = BASS_StreamCreateFile(
+            0,
+            file.as_ptr().cast::<c_void>(),
+            0,
+            0,
+            BASS_STREAM_DECODE | BASS_SAMPLE_FLOAT | BASS_UNICODE,
+        );
+
+        if handle == 0 {
+            get_bass_error("Failed to create decoding stream")?;
+        }
+
+        let mut result = Self {
+            channels: 0,
+            handle,
+            sample_rate: 0,
+        };
+
+        let mut info = BassChannelInfo::new(0, 0, 0, 0, 0, 0, 0, null());
+        if BASS_ChannelGetInfo(handle, &mut info) == 0 {
+            get_bass_error("Failed to get decoding stream info")?;
+        }
+        result.channels = info.channels as u16;
+        result.sample_rate = info.default_frequency;
+
+        Ok(result)
+    }
+
+    pub fn free(&mut self) -> Result<()> {
+        if BASS_StreamFree(self.handle) == 0 {
+            get_bass_error("Failed to free decoding stream")?;
+        }
+        self.handle = 0;
+
+        Ok(())
+    }
+}
+
+impl AudioDecoder for BassDecoder {
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn read(&mut self, buffer: &mut [f32]) -> Result<usize> {
+        let length = size_of_val(buffer) as u32;
+        let result = BASS_ChannelGetData(
+            self.handle,
+            buffer.as_mut_ptr().cast::<c_void>(),
+            length | BASS_DATA_FLOAT,
+        );
+
+        if result == u32::MAX {
+            if BASS_ErrorGetCode() == BASS_ERROR_ENDED {
+                return Ok(0);
+            }
+            get_bass_error("Failed to decode stream")?;
+        }
+
+        Ok(result as usize / size_of::<f32>())
+    }
+}
+
+impl Drop for BassDecoder {
+    fn drop(&mut self) {
+        if let Err(e) = self.free() {
+            error!("{:#?}", e);
+        }
+    }
+}
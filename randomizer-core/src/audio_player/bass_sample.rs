@@ -0,0 +1,93 @@
+use std::{ffi::c_void, path::Path};
+
+use bass_sys::{
+    BASS_ChannelGetAttribute, BASS_ChannelPlay, BASS_ChannelSetAttribute, BASS_SampleFree,
+    BASS_SampleGetChannel, BASS_SampleLoad, BASS_ATTRIB_FREQ, BASS_ATTRIB_VOL,
+    BASS_SAMPLE_OVER_POS, BASS_UNICODE, HSAMPLE,
+};
+use log::error;
+use widestring::U16CString;
+
+use crate::Result;
+
+use super::{
+    backend::AudioSample,
+    bass_backend::{register_channel, unregister_channel},
+    error::get_bass_error,
+};
+
+/// Maximum number of simultaneous playbacks, the longest playing one is replaced
+const MAX_PLAYBACKS: u32 = 8;
+
+pub struct BassSample {
+    handle: HSAMPLE,
+}
+
+impl BassSample {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let file = U16CString::from_str(path.to_string_lossy())?;
+        let handle = BASS_SampleLoad(
+            0,
+            file.as_ptr().cast::<c_void>(),
+            0,
+            0,
+            MAX_PLAYBACKS,
+            BASS_UNICODE | BASS_SAMPLE_OVER_POS,
+        );
+
+        if handle == 0 {
+            get_bass_error("Failed to load sample")?;
+        }
+
+        register_channel(handle);
+
+        Ok(Self { handle })
+    }
+
+    pub fn free(&mut self) -> Result<()> {
+        unregister_channel(self.handle);
+        if BASS_SampleFree(self.handle) == 0 {
+            get_bass_error("Failed to free sample")?;
+        }
+        self.handle = 0;
+
+        Ok(())
+    }
+}
+
+impl AudioSample for BassSample {
+    fn play(&self, volume: f32, speed: f32) -> Result<()> {
+        let channel = BASS_SampleGetChannel(self.handle, 0);
+        if channel == 0 {
+            get_bass_error("Failed to get sample channel")?;
+        }
+
+        if BASS_ChannelSetAttribute(channel, BASS_ATTRIB_VOL, volume) == 0 {
+            get_bass_error("Failed to set volume for sample")?;
+        }
+
+        if speed != 1.0 {
+            let mut frequency = 0.0;
+            if BASS_ChannelGetAttribute(channel, BASS_ATTRIB_FREQ, &mut frequency) == 0 {
+                get_bass_error("Failed to get sample frequency")?;
+            }
+            if BASS_ChannelSetAttribute(channel, BASS_ATTRIB_FREQ, frequency * speed) == 0 {
+                get_bass_error("Failed to set sample frequency")?;
+            }
+        }
+
+        if BASS_ChannelPlay(channel, 1) == 0 {
+            get_bass_error("Failed to play sample")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for BassSample {
+    fn drop(&mut self) {
+        if let Err(e) = self.free() {
+            error!("{:#?}", e);
+        }
+    }
+}
@@ -1,33 +1,58 @@
-use std::ffi::c_void;
+use std::{ffi::c_void, path::Path, sync::Arc};
 
 use bass_sys::{
-    BASS_ChannelPlay, BASS_ChannelSetSync, BASS_ChannelSlideAttribute, BASS_StreamCreateFile,
-    BASS_StreamFree, BASS_ATTRIB_VOL, BASS_SAMPLE_LOOP, BASS_UNICODE, HSTREAM, SYNCPROC,
+    BASS_ChannelBytes2Seconds, BASS_ChannelGetAttribute, BASS_ChannelGetData,
+    BASS_ChannelGetPosition, BASS_ChannelPlay, BASS_ChannelSeconds2Bytes, BASS_ChannelSetAttribute,
+    BASS_ChannelSetPosition, BASS_ChannelSetSync, BASS_ChannelSlideAttribute,
+    BASS_StreamCreateFile, BASS_StreamFree, BASS_ATTRIB_FREQ, BASS_ATTRIB_VOL, BASS_DATA_FFT512,
+    BASS_POS_BYTE, BASS_POS_END, BASS_POS_LOOP, BASS_SAMPLE_LOOP, BASS_SYNC_END, BASS_SYNC_ONETIME,
+    BASS_SYNC_SLIDE, BASS_UNICODE, DWORD, HSTREAM, HSYNC, SYNCPROC,
 };
 use log::error;
 use widestring::U16CString;
 
 use crate::Result;
 
-use super::error::get_bass_error;
+use super::{
+    backend::{AudioStream, LoopSection, SyncCallback, SyncType},
+    bass_backend::{register_channel, unregister_channel},
+    error::get_bass_error,
+};
+
+/// Number of magnitudes in 512 sample FFT, the bins above half the sample rate are mirrored
+const FFT_BINS: usize = 256;
+
+/// Where the stream reads encoded audio from, BASS reads it while the stream plays
+enum StreamSource {
+    File(U16CString),
+    Memory(Arc<[u8]>),
+}
 
 pub struct BassStream {
-    file: U16CString,
+    // Boxed twice so the pointer passed to BASS stays valid while the vector grows
+    #[allow(clippy::vec_box)]
+    callbacks: Vec<Box<SyncCallback>>,
+    /// Original sample rate of the stream
+    frequency: f32,
     handle: HSTREAM,
+    source: StreamSource,
 }
 
 impl BassStream {
-    pub fn from_file(path: &str, loop_stream: bool) -> Result<Self> {
-        let mut result = Self {
-            file: U16CString::from_str(path)?,
-            handle: 0,
-        };
-        result.create_stream(loop_stream)?;
+    pub fn from_file(path: &Path, loop_section: Option<LoopSection>) -> Result<Self> {
+        Self::new(
+            StreamSource::File(U16CString::from_str(path.to_string_lossy())?),
+            loop_section,
+        )
+    }
 
-        Ok(result)
+    /// Plays encoded file contents from memory, the stream keeps them alive while it plays
+    pub fn from_memory(data: Arc<[u8]>, loop_section: Option<LoopSection>) -> Result<Self> {
+        Self::new(StreamSource::Memory(data), loop_section)
     }
 
     pub fn free(&mut self) -> Result<()> {
+        unregister_channel(self.handle);
         if BASS_StreamFree(self.handle) == 0 {
             get_bass_error("Failed to free stream")?;
         }
@@ -36,7 +61,81 @@ impl BassStream {
         Ok(())
     }
 
-    pub fn play(&self, restart: bool) -> Result<()> {
+    fn new(source: StreamSource, loop_section: Option<LoopSection>) -> Result<Self> {
+        let mut result = Self {
+            callbacks: vec![],
+            frequency: 0.0,
+            handle: 0,
+            source,
+        };
+        result.create_stream(loop_section)?;
+
+        Ok(result)
+    }
+
+    fn create_stream(&mut self, loop_section: Option<LoopSection>) -> Result<()> {
+        let mut flags = 0;
+        if loop_section.is_some() {
+            flags |= BASS_SAMPLE_LOOP;
+        }
+        let handle = match &self.source {
+            StreamSource::File(file) => BASS_StreamCreateFile(
+                0,
+                file.as_ptr().cast::<c_void>(),
+                0,
+                0,
+                flags | BASS_UNICODE,
+            ),
+            StreamSource::Memory(data) => BASS_StreamCreateFile(
+                1,
+                data.as_ptr().cast::<c_void>(),
+                0,
+                data.len() as u64,
+                flags,
+            ),
+        };
+
+        if handle == 0 {
+            get_bass_error("Failed to create stream")?;
+        }
+
+        self.handle = handle;
+        register_channel(handle);
+
+        if BASS_ChannelGetAttribute(self.handle, BASS_ATTRIB_FREQ, &mut self.frequency) == 0 {
+            get_bass_error("Failed to get stream frequency")?;
+        }
+
+        if let Some(section) = loop_section {
+            if section.start > 0.0 {
+                self.set_position(section.start, BASS_POS_LOOP)?;
+            }
+            // The stream ends at the loop end, so the loop goes back from there
+            if let Some(end) = section.end {
+                self.set_position(end, BASS_POS_END)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets position of the given kind, e.g. playback or loop start, in seconds
+    fn set_position(&self, seconds: f64, mode: DWORD) -> Result<()> {
+        let position = BASS_ChannelSeconds2Bytes(self.handle, seconds);
+        if position == u64::MAX {
+            get_bass_error("Failed to convert stream position")?;
+        }
+
+        if BASS_ChannelSetPosition(self.handle, position, mode) == 0 {
+            get_bass_error("Failed to set stream position")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl AudioStream for BassStream {
+    fn play(&self, restart: bool) -> Result<()> {
         if BASS_ChannelPlay(self.handle, restart.into()) == 0 {
             get_bass_error("Failed to play stream")?;
         }
@@ -44,21 +143,55 @@ impl BassStream {
         Ok(())
     }
 
-    pub fn set_sync(
-        &self,
-        sync_type: u32,
-        parameter: u64,
-        proc: *mut SYNCPROC,
-        user: *mut c_void,
-    ) -> Result<()> {
-        if BASS_ChannelSetSync(self.handle, sync_type, parameter, proc, user) == 0 {
-            get_bass_error("Failed to set device for stream")?;
+    fn set_volume(&self, volume: f32) -> Result<()> {
+        if BASS_ChannelSetAttribute(self.handle, BASS_ATTRIB_VOL, volume) == 0 {
+            get_bass_error("Failed to set volume for stream")?;
         }
 
         Ok(())
     }
 
-    pub fn slide_volume(&self, volume: f32, time: u32) -> Result<()> {
+    fn position(&self) -> Result<f64> {
+        let position = BASS_ChannelGetPosition(self.handle, BASS_POS_BYTE);
+        if position == u64::MAX {
+            get_bass_error("Failed to get stream position")?;
+        }
+
+        let result = BASS_ChannelBytes2Seconds(self.handle, position);
+        if result < 0.0 {
+            get_bass_error("Failed to convert stream position")?;
+        }
+
+        Ok(result)
+    }
+
+    fn seek(&self, position: f64) -> Result<()> {
+        self.set_position(position, BASS_POS_BYTE)
+    }
+
+    fn fft(&self) -> Result<Vec<f32>> {
+        let mut result = vec![0.0f32; FFT_BINS];
+        if BASS_ChannelGetData(
+            self.handle,
+            result.as_mut_ptr().cast::<c_void>(),
+            BASS_DATA_FFT512,
+        ) == u32::MAX
+        {
+            get_bass_error("Failed to get FFT data for stream")?;
+        }
+
+        Ok(result)
+    }
+
+    fn set_speed(&self, speed: f32) -> Result<()> {
+        if BASS_ChannelSetAttribute(self.handle, BASS_ATTRIB_FREQ, self.frequency * speed) == 0 {
+            get_bass_error("Failed to set speed for stream")?;
+        }
+
+        Ok(())
+    }
+
+    fn slide_volume(&self, volume: f32, time: u32) -> Result<()> {
         if BASS_ChannelSlideAttribute(self.handle, BASS_ATTRIB_VOL, volume, time) == 0 {
             get_bass_error("Failed to slide volume for stream")?;
         }
@@ -66,18 +199,25 @@ impl BassStream {
         Ok(())
     }
 
-    fn create_stream(&mut self, loop_stream: bool) -> Result<()> {
-        let mut flags = BASS_UNICODE;
-        if loop_stream {
-            flags |= BASS_SAMPLE_LOOP;
-        }
-        let handle = BASS_StreamCreateFile(0, self.file.as_ptr().cast::<c_void>(), 0, 0, flags);
+    fn set_sync(&mut self, sync_type: SyncType, callback: SyncCallback) -> Result<()> {
+        let sync_type = match sync_type {
+            SyncType::End => BASS_SYNC_END,
+            SyncType::Slide => BASS_SYNC_SLIDE,
+        };
 
-        if handle == 0 {
-            get_bass_error("Failed to create stream")?;
+        let callback = Box::new(callback);
+        let user = &*callback as *const SyncCallback as *mut c_void;
+        if BASS_ChannelSetSync(
+            self.handle,
+            sync_type | BASS_SYNC_ONETIME,
+            0,
+            sync_proc as *mut SYNCPROC,
+            user,
+        ) == 0
+        {
+            get_bass_error("Failed to set sync for stream")?;
         }
-
-        self.handle = handle;
+        self.callbacks.push(callback);
 
         Ok(())
     }
@@ -90,3 +230,12 @@ impl Drop for BassStream {
         }
     }
 }
+
+extern "system" fn sync_proc(_handle: HSYNC, _channel: DWORD, _data: DWORD, user: *mut c_void) {
+    if user.is_null() {
+        return;
+    }
+
+    let callback: &SyncCallback = unsafe { &*(user as *const SyncCallback) };
+    callback();
+}
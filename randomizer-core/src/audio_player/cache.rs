@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    fs::read,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread,
+};
+
+use log::error;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+use crate::Result;
+
+/// Files for the loader thread of the cache to read
+pub enum CacheRequest {
+    /// File of a stream that missed the cache, other files are evicted to make room for it
+    Load(PathBuf),
+    /// Files that are read only into free space
+    Preload(Vec<PathBuf>),
+}
+
+struct CachedFile {
+    data: Arc<[u8]>,
+    /// Use number of the last access, the smallest one is evicted first
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    files: HashMap<PathBuf, CachedFile>,
+    /// Size limit in bytes
+    limit: usize,
+    /// Total size of the cached files in bytes
+    size: usize,
+    uses: u64,
+}
+
+impl CacheState {
+    /// Evicts least recently used files until `extra` bytes fit under the limit
+    fn make_room(&mut self, extra: usize) {
+        while self.size + extra > self.limit {
+            let path = match self
+                .files
+                .iter()
+                .min_by_key(|(_, file)| file.last_used)
+                .map(|(path, _)| path.clone())
+            {
+                Some(path) => path,
+                None => break,
+            };
+            if let Some(file) = self.files.remove(&path) {
+                self.size -= file.data.len();
+            }
+        }
+    }
+
+    fn insert(&mut self, path: &Path, data: Arc<[u8]>) {
+        self.uses += 1;
+        self.size += data.len();
+        let file = CachedFile {
+            data,
+            last_used: self.uses,
+        };
+        if let Some(previous) = self.files.insert(path.to_path_buf(), file) {
+            self.size -= previous.data.len();
+        }
+    }
+}
+
+/// Audio files loaded into memory, so streams don't wait for slow drives. Least recently used
+/// files are evicted when the size limit is reached
+#[derive(Default)]
+pub struct AudioCache {
+    state: Mutex<CacheState>,
+}
+
+impl AudioCache {
+    /// Changes size limit in bytes, zero limit disables the cache
+    pub fn set_limit(&self, limit: usize) {
+        let mut state = self.lock();
+        state.limit = limit;
+        state.make_room(0);
+    }
+
+    /// Returns contents of the file if it's cached
+    pub fn get(&self, path: &Path) -> Option<Arc<[u8]>> {
+        let mut state = self.lock();
+        state.uses += 1;
+        let uses = state.uses;
+        state.files.get_mut(path).map(|file| {
+            file.last_used = uses;
+            file.data.clone()
+        })
+    }
+
+    /// Reads the file into the cache, evicting least recently used files to make room. Files
+    /// larger than the limit are not cached
+    pub fn load(&self, path: &Path) -> Result<()> {
+        if self.get(path).is_some() || !self.fits(path, false) {
+            return Ok(());
+        }
+
+        // The file is read without the lock, other streams may use the cache meanwhile
+        let data: Arc<[u8]> = read(path)?.into();
+        let mut state = self.lock();
+        if data.len() <= state.limit {
+            state.make_room(data.len());
+            state.insert(path, data);
+        }
+
+        Ok(())
+    }
+
+    /// Reads files that fit into free space of the cache, cached files are never evicted for them
+    pub fn preload(&self, paths: &[PathBuf]) {
+        for path in paths {
+            if self.lock().files.contains_key(path) || !self.fits(path, true) {
+                continue;
+            }
+
+            match read(path) {
+                Ok(data) => {
+                    let mut state = self.lock();
+                    if state.size + data.len() <= state.limit {
+                        state.insert(path, data.into());
+                    }
+                }
+                Err(e) => error!("Failed to preload {}: {:#?}", path.display(), e),
+            }
+        }
+    }
+
+    /// Checks file size against the limit, or against free space if `free_only` is set
+    fn fits(&self, path: &Path, free_only: bool) -> bool {
+        let size = match path.metadata() {
+            Ok(metadata) => metadata.len() as usize,
+            Err(_) => return false,
+        };
+        let state = self.lock();
+        if free_only {
+            state.size + size <= state.limit
+        } else {
+            size <= state.limit
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Starts the thread that reads files into the cache one at a time, so streams that miss the
+/// cache don't compete for the drive. The thread ends when the sender is dropped
+pub fn spawn_loader(cache: Arc<AudioCache>) -> UnboundedSender<CacheRequest> {
+    let (sender, mut receiver) = unbounded_channel();
+    thread::spawn(move || {
+        while let Some(request) = receiver.blocking_recv() {
+            match request {
+                CacheRequest::Load(path) => {
+                    if let Err(e) = cache.load(&path) {
+                        error!("Failed to cache {}: {:#?}", path.display(), e);
+                    }
+                }
+                CacheRequest::Preload(paths) => cache.preload(&paths),
+            }
+        }
+    });
+
+    sender
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::write, path::PathBuf};
+
+    use tempfile::TempDir;
+
+    use super::AudioCache;
+
+    /// Temporary directory with files of the given sizes in bytes
+    struct Fixture {
+        dir: TempDir,
+    }
+
+    impl Fixture {
+        fn new(files: &[(&str, usize)]) -> Self {
+            let dir = TempDir::new().unwrap();
+            for (name, size) in files {
+                write(dir.path().join(name), vec![0; *size]).unwrap();
+            }
+
+            Self { dir }
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.dir.path().join(name)
+        }
+
+        fn cached(&self, cache: &AudioCache) -> Vec<&'static str> {
+            ["a", "b", "c", "d", "big"]
+                .into_iter()
+                .filter(|x| cache.lock().files.contains_key(&self.path(x)))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let fixture = Fixture::new(&[("a", 10), ("b", 10), ("c", 10), ("d", 10)]);
+        let cache = AudioCache::default();
+        cache.set_limit(30);
+        for name in ["a", "b", "c"] {
+            cache.load(&fixture.path(name)).unwrap();
+        }
+
+        // Reading a file counts as a use, so the untouched one goes first
+        assert!(cache.get(&fixture.path("a")).is_some());
+        cache.load(&fixture.path("d")).unwrap();
+        assert_eq!(fixture.cached(&cache), ["a", "c", "d"]);
+
+        cache.load(&fixture.path("b")).unwrap();
+        assert_eq!(fixture.cached(&cache), ["a", "b", "d"]);
+    }
+
+    #[test]
+    fn skips_files_over_the_limit() {
+        let fixture = Fixture::new(&[("a", 10), ("big", 40)]);
+        let cache = AudioCache::default();
+        cache.set_limit(30);
+        cache.load(&fixture.path("a")).unwrap();
+
+        cache.load(&fixture.path("big")).unwrap();
+        cache.preload(&[fixture.path("big")]);
+
+        assert_eq!(fixture.cached(&cache), ["a"]);
+        assert_eq!(cache.lock().size, 10);
+    }
+
+    #[test]
+    fn keeps_size_under_the_limit() {
+        let fixture = Fixture::new(&[("a", 10), ("b", 10), ("c", 10), ("d", 10)]);
+        let cache = AudioCache::default();
+        cache.set_limit(30);
+
+        // Preloading fills only free space
+        cache.preload(&["a", "b", "c", "d"].map(|x| fixture.path(x)));
+        assert_eq!(fixture.cached(&cache), ["a", "b", "c"]);
+        assert_eq!(cache.lock().size, 30);
+
+        // Lowering the limit evicts until the files fit, zero disables the cache
+        cache.set_limit(15);
+        assert_eq!(fixture.cached(&cache), ["c"]);
+        assert_eq!(cache.lock().size, 10);
+        cache.set_limit(0);
+        cache.load(&fixture.path("a")).unwrap();
+        assert!(fixture.cached(&cache).is_empty());
+        assert_eq!(cache.lock().size, 0);
+    }
+}
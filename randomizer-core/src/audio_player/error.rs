@@ -1,54 +1,175 @@
+use std::fmt::{self, Display, Formatter};
+
 use bass_sys::BASS_ErrorGetCode;
 
 use crate::{Error, Result};
 
-pub(crate) fn get_bass_error(message: &str) -> Result<()> {
-    let error_code = BASS_ErrorGetCode();
-    if error_code == 0 {
-        return Ok(());
+/// Error code of the last failed BASS call
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BassErrorCode {
+    Memory,
+    FileOpen,
+    Driver,
+    BufferLost,
+    Handle,
+    Format,
+    Position,
+    Init,
+    Start,
+    Ssl,
+    Already,
+    NotAudio,
+    NoChannel,
+    IllegalType,
+    IllegalParameter,
+    No3d,
+    NoEax,
+    Device,
+    NotPlaying,
+    SampleRate,
+    NotFile,
+    NoHardware,
+    Empty,
+    NoInternet,
+    Create,
+    NoEffects,
+    NotAvailable,
+    Decode,
+    DirectX,
+    Timeout,
+    FileFormat,
+    Speaker,
+    Version,
+    Codec,
+    Ended,
+    Busy,
+    Unstreamable,
+    Unknown(i32),
+}
+
+impl BassErrorCode {
+    /// Recoverable errors concern a single file or a single attempt, e.g. unsupported format or
+    /// busy device, another track or a later attempt may play. Others mean audio doesn't work
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            Self::FileOpen
+                | Self::Format
+                | Self::Ssl
+                | Self::NotAudio
+                | Self::NoChannel
+                | Self::Empty
+                | Self::NoInternet
+                | Self::Timeout
+                | Self::FileFormat
+                | Self::Codec
+                | Self::Ended
+                | Self::Busy
+                | Self::Unstreamable
+        )
     }
 
-    let message = message.to_string();
-    let error_text = match BASS_ErrorGetCode() {
-        1 => "Memory error",
-        2 => "Can't open the file",
-        3 => "Can't find a free/valid driver",
-        4 => "The sample buffer was lost",
-        5 => "Invalid handle",
-        6 => "Unsupported sample format",
-        7 => "Invalid position",
-        8 => "BASS_Init has not been successfully called",
-        9 => "BASS_Start has not been successfully called",
-        10 => "SSL/HTTPS support isn't available",
-        14 => "Already initialized/paused/whatever",
-        17 => "File does not contain audio",
-        18 => "Can't get a free channel",
-        19 => "An illegal type was specified",
-        20 => "An illegal parameter was specified",
-        21 => "No 3D support",
-        22 => "No EAX support",
-        23 => "Illegal device number",
-        24 => "Not playing",
-        25 => "Illegal sample rate",
-        27 => "The stream is not a file stream",
-        29 => "No hardware voices available",
-        31 => "The MOD music has no sequence data",
-        32 => "No internet connection could be opened",
-        33 => "Couldn't create the file",
-        34 => "Effects are not available",
-        37 => "Requested data/action is not available",
-        38 => "The channel is/isn't a \"decoding channel\"",
-        39 => "A sufficient DirectX version is not installed",
-        40 => "Connection timed out",
-        41 => "Unsupported file format",
-        42 => "Unavailable speaker",
-        43 => "Invalid BASS version (used by add-ons)",
-        44 => "Codec is not available/supported",
-        45 => "The channel/file has ended",
-        46 => "The device is busy",
-        47 => "Unstreamable file",
-        _ => "Unknown",
-    };
-
-    Err(Error::Bass(message, error_text.to_string()))
+    fn description(&self) -> &'static str {
+        match self {
+            Self::Memory => "Memory error",
+            Self::FileOpen => "Can't open the file",
+            Self::Driver => "Can't find a free/valid driver",
+            Self::BufferLost => "The sample buffer was lost",
+            Self::Handle => "Invalid handle",
+            Self::Format => "Unsupported sample format",
+            Self::Position => "Invalid position",
+            Self::Init => "BASS_Init has not been successfully called",
+            Self::Start => "BASS_Start has not been successfully called",
+            Self::Ssl => "SSL/HTTPS support isn't available",
+            Self::Already => "Already initialized/paused/whatever",
+            Self::NotAudio => "File does not contain audio",
+            Self::NoChannel => "Can't get a free channel",
+            Self::IllegalType => "An illegal type was specified",
+            Self::IllegalParameter => "An illegal parameter was specified",
+            Self::No3d => "No 3D support",
+            Self::NoEax => "No EAX support",
+            Self::Device => "Illegal device number",
+            Self::NotPlaying => "Not playing",
+            Self::SampleRate => "Illegal sample rate",
+            Self::NotFile => "The stream is not a file stream",
+            Self::NoHardware => "No hardware voices available",
+            Self::Empty => "The MOD music has no sequence data",
+            Self::NoInternet => "No internet connection could be opened",
+            Self::Create => "Couldn't create the file",
+            Self::NoEffects => "Effects are not available",
+            Self::NotAvailable => "Requested data/action is not available",
+            Self::Decode => "The channel is/isn't a \"decoding channel\"",
+            Self::DirectX => "A sufficient DirectX version is not installed",
+            Self::Timeout => "Connection timed out",
+            Self::FileFormat => "Unsupported file format",
+            Self::Speaker => "Unavailable speaker",
+            Self::Version => "Invalid BASS version (used by add-ons)",
+            Self::Codec => "Codec is not available/supported",
+            Self::Ended => "The channel/file has ended",
+            Self::Busy => "The device is busy",
+            Self::Unstreamable => "Unstreamable file",
+            Self::Unknown(_) => "Unknown",
+        }
+    }
+}
+
+impl From<i32> for BassErrorCode {
+    fn from(code: i32) -> Self {
+        match code {
+            1 => Self::Memory,
+            2 => Self::FileOpen,
+            3 => Self::Driver,
+            4 => Self::BufferLost,
+            5 => Self::Handle,
+            6 => Self::Format,
+            7 => Self::Position,
+            8 => Self::Init,
+            9 => Self::Start,
+            10 => Self::Ssl,
+            14 => Self::Already,
+            17 => Self::NotAudio,
+            18 => Self::NoChannel,
+            19 => Self::IllegalType,
+            20 => Self::IllegalParameter,
+            21 => Self::No3d,
+            22 => Self::NoEax,
+            23 => Self::Device,
+            24 => Self::NotPlaying,
+            25 => Self::SampleRate,
+            27 => Self::NotFile,
+            29 => Self::NoHardware,
+            31 => Self::Empty,
+            32 => Self::NoInternet,
+            33 => Self::Create,
+            34 => Self::NoEffects,
+            37 => Self::NotAvailable,
+            38 => Self::Decode,
+            39 => Self::DirectX,
+            40 => Self::Timeout,
+            41 => Self::FileFormat,
+            42 => Self::Speaker,
+            43 => Self::Version,
+            44 => Self::Codec,
+            45 => Self::Ended,
+            46 => Self::Busy,
+            47 => Self::Unstreamable,
+            code => Self::Unknown(code),
+        }
+    }
+}
+
+impl Display for BassErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(code) => write!(f, "Unknown error {code}"),
+            _ => f.write_str(self.description()),
+        }
+    }
+}
+
+pub(crate) fn get_bass_error(message: &str) -> Result<()> {
+    match BASS_ErrorGetCode() {
+        0 => Ok(()),
+        code => Err(Error::Bass(message.to_string(), code.into())),
+    }
 }
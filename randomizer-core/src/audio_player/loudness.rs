@@ -0,0 +1,308 @@
+use std::{
+    collections::HashMap,
+    f64::consts::PI,
+    fs::{read_to_string, write},
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+    time::UNIX_EPOCH,
+};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+use super::backend::{AudioBackend, AudioDecoder};
+
+/// Loudness every track is brought to, in LUFS, the ReplayGain 2.0 reference level
+const TARGET_LOUDNESS: f64 = -18.0;
+/// Largest gain in dB, quiet tracks are mostly noise beyond it
+const MAX_GAIN: f64 = 12.0;
+/// Gating block is 400 ms long and starts every 100 ms
+const SUB_BLOCKS_PER_BLOCK: usize = 4;
+const SUB_BLOCKS_PER_SECOND: u32 = 10;
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+/// Number of analysed files between cache saves
+const SAVE_INTERVAL: usize = 10;
+
+/// Loudness gains of files by path, as linear volume factors
+pub(super) type Gains = Arc<RwLock<HashMap<PathBuf, f32>>>;
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Analysis {
+    /// Integrated loudness in LUFS, `None` for silent files
+    loudness: Option<f64>,
+    /// Modification time of the analysed file in seconds since the epoch
+    modified: u64,
+    /// Sample peak in 0-1 range
+    peak: f64,
+}
+
+impl Analysis {
+    /// Returns volume factor that brings the file to the target loudness without clipping
+    fn gain(&self) -> f32 {
+        let loudness = match self.loudness {
+            Some(loudness) => loudness,
+            None => return 1.0,
+        };
+        let mut gain = (TARGET_LOUDNESS - loudness).clamp(-MAX_GAIN, MAX_GAIN);
+        if self.peak > 0.0 {
+            gain = gain.min(-20.0 * self.peak.log10());
+        }
+
+        10f64.powf(gain / 20.0) as f32
+    }
+}
+
+/// Measures loudness of the files in the background and puts their gains into `gains`. Results
+/// are cached in `cache_path`, unchanged files are not analysed again
+pub(super) fn scan(
+    backend: Arc<dyn AudioBackend>,
+    paths: Vec<PathBuf>,
+    cache_path: Option<PathBuf>,
+    gains: Gains,
+) {
+    let mut cache: HashMap<PathBuf, Analysis> = cache_path
+        .as_deref()
+        .filter(|x| x.is_file())
+        .map(|x| {
+            read_cache(x).unwrap_or_else(|e| {
+                error!(
+                    "Failed to read loudness cache, starting over. Error: {:#?}",
+                    e
+                );
+                HashMap::new()
+            })
+        })
+        .unwrap_or_default();
+
+    let mut analysed = 0;
+    for path in paths {
+        let modified = match modified(&path) {
+            Ok(modified) => modified,
+            Err(e) => {
+                error!("{:#?}", e);
+                continue;
+            }
+        };
+
+        let analysis = match cache.get(&path) {
+            Some(analysis) if analysis.modified == modified => *analysis,
+            _ => match backend.decode(&path).and_then(|mut x| measure(x.as_mut())) {
+                Ok((loudness, peak)) => {
+                    let analysis = Analysis {
+                        loudness,
+                        modified,
+                        peak,
+                    };
+                    cache.insert(path.clone(), analysis);
+                    analysed += 1;
+                    if analysed % SAVE_INTERVAL == 0 {
+                        try_save_cache(cache_path.as_deref(), &cache);
+                    }
+                    analysis
+                }
+                Err(e) => {
+                    error!("Failed to measure loudness of {}: {:#?}", path.display(), e);
+                    continue;
+                }
+            },
+        };
+
+        gains
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(path, analysis.gain());
+    }
+
+    if analysed % SAVE_INTERVAL != 0 {
+        try_save_cache(cache_path.as_deref(), &cache);
+    }
+}
+
+fn try_save_cache(path: Option<&Path>, cache: &HashMap<PathBuf, Analysis>) {
+    if let Some(path) = path {
+        if let Err(e) = save_cache(path, cache) {
+            error!("Failed to save loudness cache: {:#?}", e);
+        }
+    }
+}
+
+/// Returns integrated loudness (ITU-R BS.1770) and sample peak of the decoded file
+fn measure(decoder: &mut dyn AudioDecoder) -> Result<(Option<f64>, f64)> {
+    let channels = usize::from(decoder.channels().max(1));
+    let sample_rate = f64::from(decoder.sample_rate().max(1));
+    let sub_block_length = (decoder.sample_rate() / SUB_BLOCKS_PER_SECOND).max(1) as usize;
+    let weights = channel_weights(channels);
+
+    let mut filters = vec![KWeighting::new(sample_rate); channels];
+    let mut buffer = vec![0.0; 4096 * channels];
+    let mut peak: f64 = 0.0;
+    // Mean square of every 100 ms, summed over channels with their weights
+    let mut sub_blocks = vec![];
+    let mut sum = 0.0;
+    let mut frames = 0;
+
+    loop {
+        let length = decoder.read(&mut buffer)?;
+        if length == 0 {
+            break;
+        }
+
+        for frame in buffer[..length].chunks(channels) {
+            for (channel, sample) in frame.iter().enumerate() {
+                let sample = f64::from(*sample);
+                peak = peak.max(sample.abs());
+                let filtered = filters[channel].process(sample);
+                sum += weights[channel] * filtered * filtered;
+            }
+
+            frames += 1;
+            if frames == sub_block_length {
+                sub_blocks.push(sum / frames as f64);
+                sum = 0.0;
+                frames = 0;
+            }
+        }
+    }
+
+    let mut blocks: Vec<f64> = sub_blocks
+        .windows(SUB_BLOCKS_PER_BLOCK)
+        .map(|x| x.iter().sum::<f64>() / SUB_BLOCKS_PER_BLOCK as f64)
+        .collect();
+    // Sounds shorter than a block are measured as a whole
+    if blocks.is_empty() {
+        let total_frames = sub_blocks.len() * sub_block_length + frames;
+        if total_frames > 0 {
+            blocks.push(
+                (sub_blocks.iter().sum::<f64>() * sub_block_length as f64 + sum)
+                    / total_frames as f64,
+            );
+        }
+    }
+
+    let above_absolute: Vec<f64> = blocks
+        .into_iter()
+        .filter(|x| to_loudness(*x) > ABSOLUTE_GATE)
+        .collect();
+    let relative_gate = match mean(&above_absolute) {
+        Some(power) => to_loudness(power) + RELATIVE_GATE,
+        None => return Ok((None, peak)),
+    };
+    let gated: Vec<f64> = above_absolute
+        .into_iter()
+        .filter(|x| to_loudness(*x) > relative_gate)
+        .collect();
+
+    Ok((mean(&gated).map(to_loudness), peak))
+}
+
+fn to_loudness(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// Surround channels of 5.1 are louder to the ear and LFE is left out
+fn channel_weights(channels: usize) -> Vec<f64> {
+    if channels == 6 {
+        vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41]
+    } else {
+        vec![1.0; channels]
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Biquad {
+    a: [f64; 2],
+    b: [f64; 3],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            a,
+            b,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// K-weighting filter of BS.1770: high shelf for the head effect, then high pass
+#[derive(Clone, Copy)]
+struct KWeighting {
+    high_pass: Biquad,
+    shelf: Biquad,
+}
+
+impl KWeighting {
+    /// Coefficients are derived for the sample rate, so they match the 48 kHz ones of the
+    /// standard
+    fn new(sample_rate: f64) -> Self {
+        let f0 = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let q = 0.7071752369554196;
+        let k = (PI * f0 / sample_rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+        let k = (PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        Self { high_pass, shelf }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(x))
+    }
+}
+
+fn modified(path: &Path) -> Result<u64> {
+    Ok(path
+        .metadata()?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs())
+}
+
+fn read_cache(path: &Path) -> Result<HashMap<PathBuf, Analysis>> {
+    Ok(toml::from_str(read_to_string(path)?.as_str())?)
+}
+
+fn save_cache(path: &Path, cache: &HashMap<PathBuf, Analysis>) -> Result<()> {
+    write(path, toml::to_string(cache)?)?;
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use std::path::Path;
+
+use lofty::{Accessor, AudioFile, TaggedFile, TaggedFileExt};
+use log::error;
+use serde::Serialize;
+
+use crate::PlaylistEntry;
+
+/// Tags and duration of an audio file, for credits
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackMetadata {
+    pub artist: Option<String>,
+    /// Duration in seconds, `None` if it's unknown
+    pub duration: Option<f64>,
+    pub file_name: String,
+    pub title: Option<String>,
+}
+
+impl TrackMetadata {
+    /// Reads metadata of the file, unreadable tags leave only the file name. A title from
+    /// `playlist_entry`, the entry of the playlist the file came from, takes precedence over the
+    /// tag, its duration is used only if the file has none
+    pub fn load(path: &Path, playlist_entry: Option<&PlaylistEntry>) -> Self {
+        let file = lofty::read_from_path(path)
+            .map_err(|e| error!("Failed to read tags of {}: {:#?}", path.display(), e))
+            .ok();
+        Self::with_tags(path, file.as_ref(), playlist_entry)
+    }
+
+    /// Like `load`, but takes the tags already read from the file, `None` if they couldn't be
+    /// read
+    pub(crate) fn with_tags(
+        path: &Path,
+        file: Option<&TaggedFile>,
+        playlist_entry: Option<&PlaylistEntry>,
+    ) -> Self {
+        let tag = file.and_then(|x| x.primary_tag().or_else(|| x.first_tag()));
+        let duration = file.map(|x| x.properties().duration().as_secs_f64());
+        let mut result = Self {
+            artist: tag.and_then(|x| x.artist()).map(|x| x.to_string()),
+            duration: duration.filter(|x| *x > 0.0),
+            file_name: path
+                .file_name()
+                .map(|x| x.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            title: tag.and_then(|x| x.title()).map(|x| x.to_string()),
+        };
+        if let Some(entry) = playlist_entry {
+            result.title = entry.title.clone().or(result.title);
+            result.duration = result.duration.or(entry.duration);
+        }
+
+        result
+    }
+}
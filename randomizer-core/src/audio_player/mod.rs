@@ -1,6 +1,27 @@
+mod backend;
+mod bass_backend;
+mod bass_decoder;
+mod bass_sample;
 mod bass_stream;
+mod cache;
 mod error;
+mod loudness;
+mod metadata;
+mod null_backend;
 mod player;
+#[cfg(feature = "rodio")]
+mod rodio_backend;
 
+pub use backend::{
+    AudioBackend, AudioDecoder, AudioSample, AudioStream, LoopSection, SyncCallback, SyncType,
+};
+pub use bass_backend::BassBackend;
+pub use bass_decoder::BassDecoder;
+pub use bass_sample::BassSample;
 pub use bass_stream::BassStream;
-pub use player::Player;
+pub use error::BassErrorCode;
+pub use metadata::TrackMetadata;
+pub use null_backend::{AudioCall, NullBackend};
+pub use player::{Channel, Player};
+#[cfg(feature = "rodio")]
+pub use rodio_backend::RodioBackend;
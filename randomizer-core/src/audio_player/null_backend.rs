@@ -0,0 +1,252 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use crate::Result;
+
+use super::backend::{
+    spawn_callbacks, AudioBackend, AudioDecoder, AudioSample, AudioStream, LoopSection,
+    SyncCallback, SyncType,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AudioCall {
+    CreateStream(PathBuf, Option<LoopSection>),
+    CreateStreamFromMemory(PathBuf, Option<LoopSection>),
+    Decode(PathBuf),
+    LoadSample(PathBuf),
+    Play(PathBuf),
+    PlaySample(PathBuf, f32, f32),
+    Seek(PathBuf, f64),
+    SetDevice(String),
+    SetStreamSpeed(PathBuf, f32),
+    SetStreamVolume(PathBuf, f32),
+    SetVolume(u32),
+    SlideVolume(PathBuf, f32, u32),
+}
+
+/// Backend without audio output that records calls, sync callbacks are called as if playback
+/// happened instantly
+#[derive(Clone, Default)]
+pub struct NullBackend {
+    calls: Arc<Mutex<Vec<AudioCall>>>,
+}
+
+impl NullBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns recorded calls, clones of the backend share them
+    pub fn calls(&self) -> Vec<AudioCall> {
+        self.calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn record(&self, call: AudioCall) {
+        record(&self.calls, call);
+    }
+}
+
+impl AudioBackend for NullBackend {
+    fn list_devices(&self) -> Result<Vec<(String, String)>> {
+        Ok(vec![])
+    }
+
+    fn set_device(&self, driver: &str) -> Result<()> {
+        self.record(AudioCall::SetDevice(driver.to_string()));
+        Ok(())
+    }
+
+    fn set_volume(&self, volume: u32) -> Result<()> {
+        self.record(AudioCall::SetVolume(volume));
+        Ok(())
+    }
+
+    fn create_stream(
+        &self,
+        path: &Path,
+        loop_section: Option<LoopSection>,
+    ) -> Result<Box<dyn AudioStream>> {
+        self.record(AudioCall::CreateStream(path.to_path_buf(), loop_section));
+        Ok(Box::new(NullStream::new(
+            self,
+            path,
+            loop_section.is_some(),
+        )))
+    }
+
+    fn create_stream_from_memory(
+        &self,
+        path: &Path,
+        _data: Arc<[u8]>,
+        loop_section: Option<LoopSection>,
+    ) -> Result<Box<dyn AudioStream>> {
+        self.record(AudioCall::CreateStreamFromMemory(
+            path.to_path_buf(),
+            loop_section,
+        ));
+        Ok(Box::new(NullStream::new(
+            self,
+            path,
+            loop_section.is_some(),
+        )))
+    }
+
+    fn load_sample(&self, path: &Path) -> Result<Box<dyn AudioSample>> {
+        self.record(AudioCall::LoadSample(path.to_path_buf()));
+        Ok(Box::new(NullSample {
+            calls: self.calls.clone(),
+            path: path.to_path_buf(),
+        }))
+    }
+
+    fn decode(&self, path: &Path) -> Result<Box<dyn AudioDecoder>> {
+        self.record(AudioCall::Decode(path.to_path_buf()));
+        Ok(Box::new(NullDecoder))
+    }
+}
+
+type Callbacks = Arc<Mutex<Vec<SyncCallback>>>;
+
+struct NullStream {
+    calls: Arc<Mutex<Vec<AudioCall>>>,
+    end_callbacks: Callbacks,
+    loop_stream: bool,
+    path: PathBuf,
+    slide_callbacks: Callbacks,
+}
+
+impl NullStream {
+    fn new(backend: &NullBackend, path: &Path, loop_stream: bool) -> Self {
+        Self {
+            calls: backend.calls.clone(),
+            end_callbacks: Arc::new(Mutex::new(vec![])),
+            loop_stream,
+            path: path.to_path_buf(),
+            slide_callbacks: Arc::new(Mutex::new(vec![])),
+        }
+    }
+}
+
+impl AudioStream for NullStream {
+    fn play(&self, _restart: bool) -> Result<()> {
+        record(&self.calls, AudioCall::Play(self.path.clone()));
+        if !self.loop_stream {
+            fire(&self.end_callbacks, 0);
+        }
+
+        Ok(())
+    }
+
+    fn set_volume(&self, volume: f32) -> Result<()> {
+        record(
+            &self.calls,
+            AudioCall::SetStreamVolume(self.path.clone(), volume),
+        );
+
+        Ok(())
+    }
+
+    fn position(&self) -> Result<f64> {
+        Ok(0.0)
+    }
+
+    fn seek(&self, position: f64) -> Result<()> {
+        record(&self.calls, AudioCall::Seek(self.path.clone(), position));
+
+        Ok(())
+    }
+
+    fn fft(&self) -> Result<Vec<f32>> {
+        Ok(vec![])
+    }
+
+    fn set_speed(&self, speed: f32) -> Result<()> {
+        record(
+            &self.calls,
+            AudioCall::SetStreamSpeed(self.path.clone(), speed),
+        );
+
+        Ok(())
+    }
+
+    fn slide_volume(&self, volume: f32, time: u32) -> Result<()> {
+        record(
+            &self.calls,
+            AudioCall::SlideVolume(self.path.clone(), volume, time),
+        );
+        fire(&self.slide_callbacks, time);
+
+        Ok(())
+    }
+
+    fn set_sync(&mut self, sync_type: SyncType, callback: SyncCallback) -> Result<()> {
+        let callbacks = match sync_type {
+            SyncType::End => &self.end_callbacks,
+            SyncType::Slide => &self.slide_callbacks,
+        };
+        callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(callback);
+
+        Ok(())
+    }
+}
+
+/// Decoder of a file without samples
+struct NullDecoder;
+
+impl AudioDecoder for NullDecoder {
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        44100
+    }
+
+    fn read(&mut self, _buffer: &mut [f32]) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+struct NullSample {
+    calls: Arc<Mutex<Vec<AudioCall>>>,
+    path: PathBuf,
+}
+
+impl AudioSample for NullSample {
+    fn play(&self, volume: f32, speed: f32) -> Result<()> {
+        record(
+            &self.calls,
+            AudioCall::PlaySample(self.path.clone(), volume, speed),
+        );
+
+        Ok(())
+    }
+}
+
+fn record(calls: &Mutex<Vec<AudioCall>>, call: AudioCall) {
+    calls
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(call);
+}
+
+/// Calls one-time callbacks after `delay` milliseconds, like an audio library would
+fn fire(callbacks: &Callbacks, delay: u32) {
+    spawn_callbacks(
+        callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .drain(..)
+            .collect(),
+        Duration::from_millis(delay.into()),
+    );
+}
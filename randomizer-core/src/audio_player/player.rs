@@ -1,200 +1,345 @@
 use std::{
-    env::consts::DLL_EXTENSION,
-    ffi::{c_void, CStr, CString},
-    ops::Drop,
-    path::Path,
-    ptr::null_mut,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError},
+    thread,
 };
 
-use bass_sys::{
-    BASS_Free, BASS_GetDevice, BASS_GetDeviceInfo, BASS_Init, BASS_PluginFree, BASS_PluginLoad,
-    BASS_SetConfig, BASS_SetDevice, BassDeviceInfo, BASS_CONFIG_GVOL_STREAM, BASS_CONFIG_UNICODE,
-    BASS_DEVICE_ENABLED,
-};
 use log::error;
-use walkdir::WalkDir;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::Result;
 
-use super::{error::get_bass_error, BassStream};
+use super::{
+    backend::{AudioBackend, AudioSample, AudioStream, LoopSection, SyncCallback, SyncType},
+    cache::{spawn_loader, AudioCache, CacheRequest},
+    loudness::{scan, Gains},
+};
+
+/// Independent playback slot of the player, every channel has its own stream and volume
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Channel {
+    /// Roll music
+    Music,
+    /// Short sounds on top of the music, e.g. stop sounds
+    Stinger,
+    /// Wheel ticks
+    Tick,
+}
+
+/// Stream created by the player, it plays with the loudness gain of its file
+pub struct Track {
+    gain: f32,
+    stream: Box<dyn AudioStream>,
+}
+
+impl Track {
+    /// Registers one-time callback, it is called from the audio thread
+    pub fn set_sync(&mut self, sync_type: SyncType, callback: SyncCallback) -> Result<()> {
+        self.stream.set_sync(sync_type, callback)
+    }
+
+    /// Moves playback to `position` seconds
+    pub fn seek(&self, position: f64) -> Result<()> {
+        self.stream.seek(position)
+    }
+}
+
+struct ChannelState {
+    /// Loudness gain of the playing track
+    gain: f32,
+    stream: Option<Box<dyn AudioStream>>,
+    volume: f32,
+}
+
+impl ChannelState {
+    fn stream_volume(&self) -> f32 {
+        self.volume * self.gain
+    }
+}
+
+impl Default for ChannelState {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            stream: None,
+            volume: 1.0,
+        }
+    }
+}
 
 pub struct Player {
-    stream: Option<BassStream>,
+    // Shared with loudness scans
+    backend: Arc<dyn AudioBackend>,
+    cache: Arc<AudioCache>,
+    /// Requests for the thread that reads files into the cache
+    cache_loader: UnboundedSender<CacheRequest>,
+    channels: HashMap<Channel, ChannelState>,
+    gains: Gains,
+    /// Apply loudness gains
+    normalize: bool,
+    samples: HashMap<PathBuf, Box<dyn AudioSample>>,
 }
 
 impl Player {
-    pub fn new() -> Self {
-        Self { stream: None }
+    pub fn new(backend: Box<dyn AudioBackend>) -> Self {
+        let cache = Arc::new(AudioCache::default());
+        Self {
+            backend: Arc::from(backend),
+            cache_loader: spawn_loader(cache.clone()),
+            cache,
+            channels: HashMap::new(),
+            gains: Gains::default(),
+            normalize: false,
+            samples: HashMap::new(),
+        }
     }
 
-    pub fn find_device_index(name: &str) -> Result<u32> {
-        let mut i = 1;
-        let mut device_info = BassDeviceInfo::new(null_mut::<c_void>(), null_mut::<c_void>(), 0);
-
-        if name.is_empty() {
-            return Ok(1);
+    /// Creates stream from the cached file if it's cached, otherwise from the disk and caches
+    /// the file in the background for the next time
+    pub fn create_stream(&self, path: &Path, loop_section: Option<LoopSection>) -> Result<Track> {
+        let gain = self.gain(path);
+        if let Some(data) = self.cache.get(path) {
+            return Ok(Track {
+                gain,
+                stream: self
+                    .backend
+                    .create_stream_from_memory(path, data, loop_section)?,
+            });
         }
 
-        loop {
-            if BASS_GetDeviceInfo(i, &mut device_info) == 0 {
-                break;
-            }
+        let result = Track {
+            gain,
+            stream: self.backend.create_stream(path, loop_section)?,
+        };
+        self.request_cache(CacheRequest::Load(path.to_path_buf()));
 
-            if device_info.driver.is_null() {
-                continue;
-            }
+        Ok(result)
+    }
 
-            let c_str: &CStr = unsafe { CStr::from_ptr(device_info.driver.cast()) };
-            if c_str.to_str()? == name {
-                return Ok(i);
-            }
+    /// Reads files into the cache in the background as long as there is free space
+    pub fn cache_files(&self, paths: Vec<PathBuf>) {
+        self.request_cache(CacheRequest::Preload(paths));
+    }
 
-            i += 1;
+    fn request_cache(&self, request: CacheRequest) {
+        if self.cache_loader.send(request).is_err() {
+            error!("Audio cache loader has stopped");
         }
+    }
 
-        Ok(1)
+    pub fn list_devices(&self) -> Result<Vec<(String, String)>> {
+        self.backend.list_devices()
     }
 
-    pub fn set_device(device: u32) -> Result<()> {
-        let old_device = BASS_GetDevice();
+    pub fn set_device(&self, driver: &str) -> Result<()> {
+        self.backend.set_device(driver)
+    }
 
-        if device == old_device {
-            return Ok(());
-        }
+    /// Measures loudness of the files in the background, their gains are applied as they're
+    /// measured. Measurements are cached in `cache_path`
+    pub fn scan_loudness(&self, paths: Vec<PathBuf>, cache_path: Option<PathBuf>) {
+        let backend = self.backend.clone();
+        let gains = self.gains.clone();
+        thread::spawn(move || scan(backend, paths, cache_path, gains));
+    }
 
-        Player::init_device(device as i32)?;
+    /// Turns loudness normalization on or off, playing streams keep their volume
+    pub fn set_normalize(&mut self, normalize: bool) {
+        self.normalize = normalize;
+    }
 
-        if BASS_SetDevice(old_device) == 0 {
-            get_bass_error("Failed to set default device")?;
-        }
-        if BASS_Free() == 0 {
-            get_bass_error("Failed to free device")?;
-        }
-        if BASS_SetDevice(device) == 0 {
-            get_bass_error("Failed to set default device")?;
-        }
+    /// Sets size limit of the audio cache in megabytes, zero disables the cache
+    pub fn set_cache_size(&self, size: u32) {
+        self.cache.set_limit(size as usize * 1024 * 1024);
+    }
 
-        Ok(())
+    pub fn set_volume(&self, volume: u32) -> Result<()> {
+        self.backend.set_volume(volume)
     }
 
-    pub fn set_volume(volume: u32) -> Result<()> {
-        if BASS_SetConfig(BASS_CONFIG_GVOL_STREAM, volume.min(100) * 100) == 0 {
-            get_bass_error("Failed to set volume")?;
+    /// Sets volume of the channel, `volume` is in 0-100 range
+    pub fn set_channel_volume(&mut self, channel: Channel, volume: u32) -> Result<()> {
+        let state = self.channels.entry(channel).or_default();
+        state.volume = volume.min(100) as f32 / 100.0;
+        if let Some(stream) = &state.stream {
+            stream.set_volume(state.stream_volume())?;
         }
+
         Ok(())
     }
 
-    pub fn init(plugins_dir: impl AsRef<Path>, device: &str, volume: u32) -> Result<()> {
-        if BASS_SetConfig(BASS_CONFIG_UNICODE, 1) == 0 {
-            get_bass_error("Failed to enable Unicode for device information")?;
+    /// Lowers volume of the channel to `level` of its volume over `time` milliseconds
+    pub fn duck(&self, channel: Channel, level: f32, time: u32) -> Result<()> {
+        if let Some(state) = self.channels.get(&channel) {
+            if let Some(stream) = &state.stream {
+                stream.slide_volume(state.stream_volume() * level, time)?;
+            }
         }
-        Player::load_plugins(plugins_dir)?;
-        Player::init_device(Player::find_device_index(device)? as i32)?;
-        Player::set_volume(volume)?;
 
         Ok(())
     }
 
-    pub fn list_devices() -> Result<Vec<(String, String)>> {
-        let mut result = vec![];
-
-        let mut i = 1;
-        let mut device_info: BassDeviceInfo = BassDeviceInfo::new(null_mut(), null_mut(), 0);
-        loop {
-            if BASS_GetDeviceInfo(i, &mut device_info) == 0 {
-                break;
-            }
+    /// Fades out the channel over `time` milliseconds and stops its stream
+    pub fn fade_out(&self, channel: Channel, time: u32) -> Result<()> {
+        if let Some(stream) = self.stream(channel) {
+            stream.slide_volume(-1.0, time)?;
+        }
 
-            if device_info.flags & BASS_DEVICE_ENABLED != 0 {
-                result.push((
-                    unsafe { CStr::from_ptr(device_info.driver.cast()) }
-                        .to_str()?
-                        .to_string(),
-                    unsafe { CStr::from_ptr(device_info.name.cast()) }
-                        .to_str()?
-                        .to_string(),
-                ));
-            }
+        Ok(())
+    }
 
-            i += 1;
+    /// Plays sample loaded by `preload` on the channel, `speed` changes its rate and pitch
+    pub fn play_sample(&self, channel: Channel, path: &Path, speed: f32) -> Result<()> {
+        if let Some(sample) = self.samples.get(path) {
+            let volume = self
+                .channels
+                .get(&channel)
+                .map_or(1.0, |state| state.volume);
+            sample.play(volume * self.gain(path), speed)?;
         }
 
-        Ok(result)
+        Ok(())
     }
 
-    pub fn fade_out(&mut self) -> Result<()> {
-        if let Some(stream) = &self.stream {
-            stream.slide_volume(-1.0, 1000)?;
+    /// Loads sample into memory unless it's already loaded
+    pub fn preload(&mut self, path: &Path) -> Result<()> {
+        if !self.samples.contains_key(path) {
+            let sample = self.backend.load_sample(path)?;
+            self.samples.insert(path.to_path_buf(), sample);
         }
 
         Ok(())
     }
 
-    pub fn play(&self, restart: bool) -> Result<()> {
-        if let Some(stream) = &self.stream {
-            stream.play(restart)?;
+    /// Replaces stream of the channel and plays it, fading in over `fade_in` milliseconds
+    pub fn play(&mut self, channel: Channel, track: Track, fade_in: u32) -> Result<()> {
+        let state = self.channels.entry(channel).or_default();
+        state.gain = track.gain;
+        let stream = track.stream;
+        if fade_in > 0 {
+            stream.set_volume(0.0)?;
+            stream.slide_volume(state.stream_volume(), fade_in)?;
+        } else {
+            stream.set_volume(state.stream_volume())?;
         }
+        stream.play(false)?;
+        state.stream = Some(stream);
 
         Ok(())
     }
 
-    pub fn set_stream(&mut self, stream: Option<BassStream>) {
-        self.stream = stream;
+    /// Returns `true` if the channel has a stream
+    pub fn is_active(&self, channel: Channel) -> bool {
+        self.stream(channel).is_some()
+    }
+
+    /// Returns playback position of the channel in seconds, `None` if the channel is empty
+    pub fn position(&self, channel: Channel) -> Result<Option<f64>> {
+        self.stream(channel)
+            .map(|stream| stream.position())
+            .transpose()
     }
 
-    pub fn stop(&mut self) {
-        self.stream = None;
+    /// Returns spectrum of the channel in `bands` frequency bands from low to high, in 0-1 range.
+    /// `None` if the channel is empty or the backend can't analyse playback
+    pub fn spectrum(&self, channel: Channel, bands: usize) -> Result<Option<Vec<f32>>> {
+        let fft = match self.stream(channel) {
+            Some(stream) => stream.fft()?,
+            None => return Ok(None),
+        };
+
+        Ok((!fft.is_empty()).then(|| group_bands(&fft, bands)))
     }
 
-    fn init_device(device: i32) -> Result<()> {
-        if BASS_Init(device, 44100, 0, null_mut::<c_void>(), null_mut::<c_void>()) == 0 {
-            get_bass_error("Failed to initialize audio device")?;
+    /// Changes playback rate and pitch of the channel, 1.0 is the original rate
+    pub fn set_speed(&self, channel: Channel, speed: f32) -> Result<()> {
+        if let Some(stream) = self.stream(channel) {
+            stream.set_speed(speed)?;
         }
 
         Ok(())
     }
 
-    fn load_plugins(plugins_dir: impl AsRef<Path>) -> Result<()> {
-        for file_path in WalkDir::new(plugins_dir)
-            .follow_links(false)
-            .max_depth(1)
-            .into_iter()
-            .filter_map(std::result::Result::ok)
-            .filter(|file| {
-                file.path()
-                    .extension()
-                    .map_or(false, |ext| ext == DLL_EXTENSION)
-            })
+    /// Registers one-time callback for the stream of the channel, returns `false` if the channel
+    /// is empty
+    pub fn set_sync(
+        &mut self,
+        channel: Channel,
+        sync_type: SyncType,
+        callback: SyncCallback,
+    ) -> Result<bool> {
+        match self
+            .channels
+            .get_mut(&channel)
+            .and_then(|state| state.stream.as_mut())
         {
-            let c_path = CString::new(file_path.path().to_string_lossy().as_ref())?;
-            let handle = BASS_PluginLoad(c_path.as_ptr().cast::<c_void>(), 0);
-            if handle == 0 {
-                get_bass_error("Failed to load BASS plugin")?;
+            Some(stream) => {
+                stream.set_sync(sync_type, callback)?;
+                Ok(true)
             }
+            None => Ok(false),
         }
-
-        Ok(())
     }
 
-    fn free(&mut self) -> Result<()> {
-        self.stream = None;
+    pub fn stop(&mut self, channel: Channel) {
+        if let Some(state) = self.channels.get_mut(&channel) {
+            state.stream = None;
+        }
+    }
 
-        if BASS_PluginFree(0) == 0 {
-            get_bass_error("Failed to free plugins")?;
+    pub fn stop_all(&mut self) {
+        for state in self.channels.values_mut() {
+            state.stream = None;
         }
+    }
 
-        if BASS_Free() == 0 {
-            get_bass_error("Failed to free BASS")?;
+    /// Returns loudness gain of the file, 1.0 if normalization is off or the file isn't measured
+    fn gain(&self, path: &Path) -> f32 {
+        if !self.normalize {
+            return 1.0;
         }
 
-        Ok(())
+        self.gains
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(path)
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    fn stream(&self, channel: Channel) -> Option<&dyn AudioStream> {
+        self.channels
+            .get(&channel)
+            .and_then(|state| state.stream.as_deref())
     }
 }
 
+/// Groups FFT bins into bands of logarithmic width, so low frequencies get as many bands as high
+/// ones. Band value is its peak magnitude, scaled up as quiet parts are heard louder
+fn group_bands(fft: &[f32], bands: usize) -> Vec<f32> {
+    // The first bin is the DC offset
+    let mut start = 1;
+    (1..=bands)
+        .map(|band| {
+            let end = (fft.len() as f32).powf(band as f32 / bands as f32) as usize;
+            let end = end.max(start + 1).min(fft.len());
+            let peak = fft
+                .get(start..end)
+                .map(|bins| bins.iter().copied().fold(0.0, f32::max))
+                .unwrap_or_default();
+            start = end;
+            (peak.sqrt() * 3.0).min(1.0)
+        })
+        .collect()
+}
+
 impl Drop for Player {
     fn drop(&mut self) {
-        if let Err(e) = self.free() {
-            error!("{:#?}", e);
-        }
+        // Streams and samples have to be freed before the backend
+        self.stop_all();
+        self.samples.clear();
     }
 }
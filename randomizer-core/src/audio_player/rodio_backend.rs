@@ -0,0 +1,619 @@
+use std::{
+    fs::File,
+    io::{BufReader, Cursor, Read, Seek},
+    mem::take,
+    path::Path,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        mpsc, Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use rodio::{
+    cpal::traits::HostTrait, source::Buffered, Decoder, Device, DeviceTrait, OutputStream,
+    OutputStreamHandle, Sample, Sink, Source, StreamError,
+};
+
+use crate::Result;
+
+use super::backend::{
+    spawn_callbacks, AudioBackend, AudioDecoder, AudioSample, AudioStream, LoopSection,
+    SyncCallback, SyncType,
+};
+
+/// Number of samples between volume and slide updates
+const UPDATE_INTERVAL: u32 = 256;
+/// Divider that converts decoded samples to -1..1 range
+const I16_SCALE: f32 = 32768.0;
+
+/// Audio backend built on rodio and symphonia, supports every format from `SOUND_EXTENSIONS`
+/// except WMA
+pub struct RodioBackend {
+    output: Arc<Mutex<Output>>,
+    volume: Arc<AtomicU32>,
+}
+
+impl RodioBackend {
+    pub fn init(device: &str, volume: u32) -> Result<Self> {
+        let result = Self {
+            output: Arc::new(Mutex::new(Output::open(device)?)),
+            volume: Arc::new(AtomicU32::new(1.0f32.to_bits())),
+        };
+        result.set_volume(volume)?;
+
+        Ok(result)
+    }
+
+    /// Returns list of output devices as (driver, name) pairs, driver is the device name
+    pub fn devices() -> Result<Vec<(String, String)>> {
+        Ok(rodio::cpal::default_host()
+            .output_devices()?
+            .filter_map(|device| device.name().ok())
+            .map(|name| (name.clone(), name))
+            .collect())
+    }
+
+    fn create_stream_from_reader<R>(
+        &self,
+        reader: R,
+        loop_section: Option<LoopSection>,
+    ) -> Result<Box<dyn AudioStream>>
+    where
+        R: Read + Seek + Send + Sync + 'static,
+    {
+        // Whole files loop in the decoder, sections are repeated from memory
+        let source: Box<dyn Source<Item = i16> + Send> = match loop_section {
+            Some(section) if section.is_whole() => Box::new(Decoder::new_looped(reader)?),
+            _ => Box::new(Decoder::new(reader)?),
+        };
+        let samples_per_second = f64::from(source.sample_rate()) * f64::from(source.channels());
+        let channels = u64::from(source.channels().max(1));
+        // Positions are rounded down to whole frames, so the channels stay in place
+        let to_samples =
+            |seconds: f64| (seconds.max(0.0) * samples_per_second) as u64 / channels * channels;
+        let section = loop_section
+            .filter(|x| !x.is_whole())
+            .map(|x| SampleSection {
+                start: to_samples(x.start),
+                end: x.end.map(to_samples),
+            });
+
+        let state = Arc::new(StreamState {
+            channels: source.channels(),
+            end_callbacks: Mutex::new(vec![]),
+            global_volume: self.volume.clone(),
+            played: AtomicU64::new(0),
+            sample_rate: source.sample_rate(),
+            skip: AtomicU64::new(0),
+            slide: Mutex::new(Slide::default()),
+            slide_callbacks: Mutex::new(vec![]),
+            speed: AtomicU32::new(1.0f32.to_bits()),
+        });
+
+        let sink = Sink::try_new(
+            &self
+                .output
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .handle,
+        )?;
+        sink.pause();
+        let source = ControlledSource {
+            body: vec![],
+            factor: 0.0,
+            inner: source,
+            played: 0,
+            replay: None,
+            section,
+            skip: 0,
+            speed: 1.0,
+            state: state.clone(),
+            stopped: false,
+            until_update: 0,
+        };
+
+        Ok(Box::new(RodioStream {
+            pending: Mutex::new(Some(source)),
+            sink,
+            state,
+        }))
+    }
+}
+
+impl AudioBackend for RodioBackend {
+    fn list_devices(&self) -> Result<Vec<(String, String)>> {
+        RodioBackend::devices()
+    }
+
+    fn set_device(&self, driver: &str) -> Result<()> {
+        let mut output = self.output.lock().unwrap_or_else(PoisonError::into_inner);
+        if output.driver != driver {
+            *output = Output::open(driver)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_volume(&self, volume: u32) -> Result<()> {
+        self.volume.store(
+            (volume.min(100) as f32 / 100.0).to_bits(),
+            Ordering::Relaxed,
+        );
+
+        Ok(())
+    }
+
+    fn create_stream(
+        &self,
+        path: &Path,
+        loop_section: Option<LoopSection>,
+    ) -> Result<Box<dyn AudioStream>> {
+        self.create_stream_from_reader(BufReader::new(File::open(path)?), loop_section)
+    }
+
+    fn create_stream_from_memory(
+        &self,
+        _path: &Path,
+        data: Arc<[u8]>,
+        loop_section: Option<LoopSection>,
+    ) -> Result<Box<dyn AudioStream>> {
+        self.create_stream_from_reader(Cursor::new(data), loop_section)
+    }
+
+    fn load_sample(&self, path: &Path) -> Result<Box<dyn AudioSample>> {
+        let source = Decoder::new(BufReader::new(File::open(path)?))?.buffered();
+        // Buffered source decodes lazily, its clones share decoded frames
+        source.clone().for_each(drop);
+
+        Ok(Box::new(RodioSample {
+            global_volume: self.volume.clone(),
+            output: self.output.clone(),
+            source,
+        }))
+    }
+
+    fn decode(&self, path: &Path) -> Result<Box<dyn AudioDecoder>> {
+        Ok(Box::new(RodioDecoder(Decoder::new(BufReader::new(
+            File::open(path)?,
+        ))?)))
+    }
+}
+
+struct RodioDecoder(Decoder<BufReader<File>>);
+
+impl AudioDecoder for RodioDecoder {
+    fn channels(&self) -> u16 {
+        self.0.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.0.sample_rate()
+    }
+
+    fn read(&mut self, buffer: &mut [f32]) -> Result<usize> {
+        let mut result = 0;
+        for (target, sample) in buffer.iter_mut().zip(&mut self.0) {
+            *target = f32::from(sample) / I16_SCALE;
+            result += 1;
+        }
+
+        Ok(result)
+    }
+}
+
+struct RodioSample {
+    global_volume: Arc<AtomicU32>,
+    output: Arc<Mutex<Output>>,
+    source: Buffered<Decoder<BufReader<File>>>,
+}
+
+impl AudioSample for RodioSample {
+    fn play(&self, volume: f32, speed: f32) -> Result<()> {
+        let volume = volume * f32::from_bits(self.global_volume.load(Ordering::Relaxed));
+        self.output
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .handle
+            .play_raw(
+                self.source
+                    .clone()
+                    .amplify(volume)
+                    .speed(speed)
+                    .convert_samples(),
+            )?;
+
+        Ok(())
+    }
+}
+
+/// Output stream that lives in its own thread, because `OutputStream` can't be sent between threads
+struct Output {
+    /// Driver of the opened device, empty when it's the default one
+    driver: String,
+    handle: OutputStreamHandle,
+    // Dropping the sender stops the thread and closes the stream
+    _shutdown: mpsc::Sender<()>,
+}
+
+impl Output {
+    fn open(driver: &str) -> Result<Self> {
+        let (handle_sender, handle_receiver) = mpsc::channel();
+        let (shutdown, shutdown_receiver) = mpsc::channel::<()>();
+
+        let device = find_device(driver);
+        // Missing device falls back to the default one, so it's opened again once it returns
+        let driver = match device {
+            Some(_) => driver.to_string(),
+            None => String::new(),
+        };
+        thread::spawn(move || {
+            let stream = match device {
+                Some(device) => OutputStream::try_from_device(&device),
+                None => OutputStream::try_default(),
+            };
+
+            match stream {
+                Ok((_stream, handle)) => {
+                    if handle_sender.send(Ok(handle)).is_ok() {
+                        let _ = shutdown_receiver.recv();
+                    }
+                }
+                Err(e) => {
+                    let _ = handle_sender.send(Err(e));
+                }
+            }
+        });
+
+        let handle = handle_receiver
+            .recv()
+            .unwrap_or(Err(StreamError::NoDevice))?;
+
+        Ok(Self {
+            driver,
+            handle,
+            _shutdown: shutdown,
+        })
+    }
+}
+
+fn find_device(name: &str) -> Option<Device> {
+    if name.is_empty() {
+        return None;
+    }
+
+    rodio::cpal::default_host()
+        .output_devices()
+        .ok()?
+        .find(|device| device.name().is_ok_and(|x| x == name))
+}
+
+struct Slide {
+    /// Slide is in progress and its callbacks haven't been called yet
+    active: bool,
+    duration: Duration,
+    from: f32,
+    /// Stop the stream when the slide is done
+    stop: bool,
+    start: Instant,
+    to: f32,
+}
+
+impl Default for Slide {
+    fn default() -> Self {
+        Self {
+            active: false,
+            duration: Duration::ZERO,
+            from: 1.0,
+            stop: false,
+            start: Instant::now(),
+            to: 1.0,
+        }
+    }
+}
+
+impl Slide {
+    fn volume(&self, now: Instant) -> f32 {
+        let elapsed = now.duration_since(self.start);
+        if elapsed >= self.duration {
+            return self.to;
+        }
+
+        self.from + (self.to - self.from) * elapsed.as_secs_f32() / self.duration.as_secs_f32()
+    }
+}
+
+struct StreamState {
+    channels: u16,
+    end_callbacks: Mutex<Vec<SyncCallback>>,
+    global_volume: Arc<AtomicU32>,
+    /// Position in the file, in samples
+    played: AtomicU64,
+    sample_rate: u32,
+    /// Number of samples to skip, set by seeking
+    skip: AtomicU64,
+    slide: Mutex<Slide>,
+    slide_callbacks: Mutex<Vec<SyncCallback>>,
+    speed: AtomicU32,
+}
+
+impl StreamState {
+    fn take_callbacks(callbacks: &Mutex<Vec<SyncCallback>>) -> Vec<SyncCallback> {
+        take(&mut *callbacks.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+struct RodioStream {
+    /// Source that waits for the first `play`, seeking it skips samples on the calling thread
+    /// instead of the mixer
+    pending: Mutex<Option<ControlledSource>>,
+    sink: Sink,
+    state: Arc<StreamState>,
+}
+
+impl AudioStream for RodioStream {
+    fn play(&self, _restart: bool) -> Result<()> {
+        if let Some(source) = self
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            self.sink.append(source);
+        }
+        self.sink.play();
+        Ok(())
+    }
+
+    fn set_volume(&self, volume: f32) -> Result<()> {
+        let mut slide = self
+            .state
+            .slide
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        slide.from = volume;
+        slide.to = volume;
+
+        Ok(())
+    }
+
+    fn position(&self) -> Result<f64> {
+        Ok(self.state.played.load(Ordering::Relaxed) as f64
+            / (f64::from(self.state.sample_rate) * f64::from(self.state.channels)).max(1.0))
+    }
+
+    fn seek(&self, position: f64) -> Result<()> {
+        // Decoders can't seek, so playback only moves forward by skipping samples
+        let channels = u64::from(self.state.channels.max(1));
+        let target = (position.max(0.0) * f64::from(self.state.sample_rate)) as u64 * channels;
+        if let Some(source) = self
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+        {
+            source.skip = target.saturating_sub(source.played);
+            source.skip_samples();
+            self.state.played.store(source.played, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        // The mixer skips the samples of a playing stream
+        let played = self.state.played.load(Ordering::Relaxed);
+        self.state
+            .skip
+            .store(target.saturating_sub(played), Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn fft(&self) -> Result<Vec<f32>> {
+        // Samples are not kept after they're played
+        Ok(vec![])
+    }
+
+    fn set_speed(&self, speed: f32) -> Result<()> {
+        self.state.speed.store(speed.to_bits(), Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn slide_volume(&self, volume: f32, time: u32) -> Result<()> {
+        let mut slide = self
+            .state
+            .slide
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        *slide = Slide {
+            active: true,
+            duration: Duration::from_millis(time.into()),
+            from: slide.volume(now),
+            stop: volume < 0.0,
+            start: now,
+            to: volume.max(0.0),
+        };
+
+        Ok(())
+    }
+
+    fn set_sync(&mut self, sync_type: SyncType, callback: SyncCallback) -> Result<()> {
+        let callbacks = match sync_type {
+            SyncType::End => &self.state.end_callbacks,
+            SyncType::Slide => &self.state.slide_callbacks,
+        };
+        callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(callback);
+
+        Ok(())
+    }
+}
+
+/// Loop section in samples
+#[derive(Clone, Copy)]
+struct SampleSection {
+    start: u64,
+    end: Option<u64>,
+}
+
+/// Applies global volume, volume slides and speed, calls sync callbacks
+struct ControlledSource {
+    /// Samples of the loop section, recorded during the first playthrough
+    body: Vec<i16>,
+    factor: f32,
+    inner: Box<dyn Source<Item = i16> + Send>,
+    /// Position in the file, in samples
+    played: u64,
+    /// Position in `body` while the loop section repeats
+    replay: Option<usize>,
+    section: Option<SampleSection>,
+    skip: u64,
+    speed: f32,
+    state: Arc<StreamState>,
+    stopped: bool,
+    until_update: u32,
+}
+
+impl ControlledSource {
+    fn update(&mut self) {
+        let mut slide = self
+            .state
+            .slide
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+
+        if slide.active && now.duration_since(slide.start) >= slide.duration {
+            spawn_callbacks(
+                StreamState::take_callbacks(&self.state.slide_callbacks),
+                Duration::ZERO,
+            );
+            slide.active = false;
+            self.stopped = slide.stop;
+        }
+
+        self.state.played.store(self.played, Ordering::Relaxed);
+        self.skip += self.state.skip.swap(0, Ordering::Relaxed);
+        self.speed = f32::from_bits(self.state.speed.load(Ordering::Relaxed));
+        self.factor =
+            slide.volume(now) * f32::from_bits(self.state.global_volume.load(Ordering::Relaxed));
+    }
+
+    /// Drops `skip` samples. Skipped samples go through the loop section too, so it's recorded
+    /// whole
+    fn skip_samples(&mut self) {
+        while self.skip > 0 {
+            self.skip -= 1;
+            if self.next_sample().is_none() {
+                self.skip = 0;
+            }
+        }
+    }
+
+    /// Returns the next sample of the file, repeating the loop section after its end
+    fn next_sample(&mut self) -> Option<i16> {
+        let section = match self.section {
+            Some(section) => section,
+            None => {
+                let sample = self.inner.next()?;
+                self.played += 1;
+                return Some(sample);
+            }
+        };
+
+        if self.replay.is_none() {
+            let sample = match section.end {
+                Some(end) if self.played >= end => None,
+                _ => self.inner.next(),
+            };
+            match sample {
+                Some(sample) => {
+                    if self.played >= section.start {
+                        self.body.push(sample);
+                    }
+                    self.played += 1;
+                    return Some(sample);
+                }
+                None => self.replay = Some(0),
+            }
+        }
+
+        let index = self.replay?;
+        let sample = *self.body.get(index)?;
+        let next = (index + 1) % self.body.len();
+        self.replay = Some(next);
+        self.played = section.start + next as u64;
+
+        Some(sample)
+    }
+}
+
+impl Iterator for ControlledSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.until_update == 0 {
+            self.update();
+            self.until_update = UPDATE_INTERVAL;
+        }
+        self.until_update -= 1;
+
+        if self.stopped {
+            return None;
+        }
+
+        self.skip_samples();
+
+        match self.next_sample() {
+            Some(sample) => Some(sample.amplify(self.factor)),
+            None => {
+                self.stopped = true;
+                spawn_callbacks(
+                    StreamState::take_callbacks(&self.state.end_callbacks),
+                    Duration::ZERO,
+                );
+                None
+            }
+        }
+    }
+}
+
+impl Source for ControlledSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        // Frames end at updates, so the mixer picks up speed changes
+        let until_update = match self.until_update {
+            0 => UPDATE_INTERVAL,
+            x => x,
+        } as usize;
+
+        // The decoder is done when the loop section repeats from memory
+        if self.replay.is_some() {
+            return Some(until_update);
+        }
+
+        Some(
+            self.inner
+                .current_frame_len()
+                .map_or(until_update, |x| x.min(until_update)),
+        )
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        (self.inner.sample_rate() as f32 * self.speed).max(1.0) as u32
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        // Repeated section never ends
+        match self.section {
+            Some(_) => None,
+            None => self.inner.total_duration(),
+        }
+    }
+}
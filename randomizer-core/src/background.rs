@@ -0,0 +1,111 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use once_cell::sync::Lazy;
+use walkdir::DirEntry;
+
+use crate::{
+    func::{data_files_for_list, dir_entry_extension, list_items},
+    list_info::ListInfo,
+    rotation::Rotation,
+    Result,
+};
+
+const BG_DIR: &str = "bg";
+
+static BG_EXTENSIONS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    HashSet::from([
+        "bmp", "gif", "jpg", "jpeg", "png", "svg", "webp", "mp4", "webm",
+    ])
+});
+
+/// Picks the next background of the list according to its rotation mode, falling back to the
+/// common backgrounds. Own backgrounds of the entries are left out. Returns the path relative
+/// to the data directory
+pub(crate) fn next_background(
+    data_path: &Path,
+    rotation: &Mutex<Rotation>,
+    list_name: &str,
+) -> Result<Option<String>> {
+    let info = ListInfo::load(data_path, list_name).background;
+    let entries = if info.winner {
+        list_entries(data_path, list_name)
+    } else {
+        HashSet::new()
+    };
+
+    // Files of the list come first, common ones are used if the list has none
+    let candidates = data_files_for_list(data_path, BG_DIR, list_name, is_background)
+        .into_iter()
+        .zip([list_name, ""])
+        .map(|(files, name)| {
+            let files: Vec<PathBuf> = files
+                .map(|x| x.into_path())
+                .filter(|x| name.is_empty() || !entries.contains(&lowercase_stem(x)))
+                .collect();
+            (name, files)
+        })
+        .find(|(_, files)| !files.is_empty());
+
+    let file_path = match candidates {
+        Some((name, files)) => rotation
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pick(info.rotation, BG_DIR, name, &files, &HashSet::new()),
+        None => None,
+    };
+
+    file_path.map(|x| relative_path(data_path, &x)).transpose()
+}
+
+/// Returns own background of the winner, a file named after the entry in `data/bg/<list>`.
+/// `None` if the list has entry backgrounds turned off or the entry has none
+pub(crate) fn winner_background(
+    data_path: &Path,
+    list_name: &str,
+    winner: &str,
+) -> Result<Option<String>> {
+    if !ListInfo::load(data_path, list_name).background.winner {
+        return Ok(None);
+    }
+
+    let winner = winner.to_lowercase();
+    // The first directory is the one of the list
+    let file_path = data_files_for_list(data_path, BG_DIR, list_name, is_background)
+        .into_iter()
+        .next()
+        .and_then(|files| {
+            files
+                .map(|x| x.into_path())
+                .find(|x| lowercase_stem(x) == winner)
+        });
+
+    file_path.map(|x| relative_path(data_path, &x)).transpose()
+}
+
+fn is_background(file: &DirEntry) -> bool {
+    BG_EXTENSIONS.contains(&dir_entry_extension(file).as_str())
+}
+
+/// Lowercase entries of the list, empty if the list can't be read
+fn list_entries(data_path: &Path, list_name: &str) -> HashSet<String> {
+    list_items(data_path, list_name)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|x| x.to_lowercase())
+        .collect()
+}
+
+fn lowercase_stem(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase()
+}
+
+fn relative_path(data_path: &Path, path: &Path) -> Result<String> {
+    Ok(path.strip_prefix(data_path)?.to_string_lossy().to_string())
+}
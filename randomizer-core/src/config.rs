@@ -14,18 +14,111 @@ use serde::{Deserialize, Serialize};
 
 use crate::{constants::CONFIG_PATH, Result};
 
-#[derive(Clone, CopyGetters, Deserialize, Getters, Serialize)]
+/// How the wheel slows down
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MotionMode {
+    /// Speed is reduced by random amounts from the configured ranges
+    #[default]
+    Random,
+    /// Speed is reduced evenly, so the wheel stops at the cue point of the roll music
+    MusicCue,
+}
+
+/// What happens to the roll music when the stop sound starts
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
+pub enum MusicTransition {
+    /// Music fades out, then the stop sound plays
+    #[default]
+    Sequential,
+    /// Music fades out while the stop sound fades in
+    Crossfade,
+    /// Music plays quieter under the stop sound and fades out after it
+    Duck,
+}
+
+/// How roll and stop sounds, and backgrounds are picked
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SoundRotation {
+    /// Any sound may play, including the previous one
+    Random,
+    /// Every sound plays once before any of them repeats
+    #[default]
+    ShuffleBag,
+    /// The sound that hasn't played for the longest time plays next
+    LeastRecentlyPlayed,
+}
+
+#[derive(Clone, CopyGetters, Deserialize, Getters, Serialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct Config {
+    /// Idle time in seconds before the attract mode starts, zero disables it
+    #[getset(get_copy = "pub")]
+    attract_delay: u32,
+    /// Memory limit for audio files kept in memory in megabytes, zero disables the cache
+    #[getset(get_copy = "pub")]
+    audio_cache_size: u32,
     #[getset(get = "pub")]
     audio_device: String,
     #[getset(get_copy = "pub")]
+    motion_mode: MotionMode,
+    #[getset(get_copy = "pub")]
     music: bool,
+    /// Volume of the ducked music in percents of the music volume
+    #[getset(get_copy = "pub")]
+    music_duck_volume: u32,
+    /// Music fade-out time in milliseconds
+    #[getset(get_copy = "pub")]
+    music_fade_out: u32,
+    /// Music playback rate follows the wheel speed
+    #[getset(get_copy = "pub")]
+    music_follow_speed: bool,
+    /// Start roll music at a random position instead of its beginning
+    #[getset(get_copy = "pub")]
+    music_random_start: bool,
+    #[getset(get_copy = "pub")]
+    music_speed_max: f64,
+    #[getset(get_copy = "pub")]
+    music_speed_min: f64,
+    #[getset(get_copy = "pub")]
+    music_transition: MusicTransition,
+    #[getset(get_copy = "pub")]
+    music_volume: u32,
+    /// Bring sounds to the same loudness, measured in the background
+    #[getset(get_copy = "pub")]
+    normalize_loudness: bool,
+    #[getset(get_copy = "pub")]
+    osc_enabled: bool,
+    #[getset(get = "pub")]
+    osc_listen_address: String,
+    #[getset(get = "pub")]
+    osc_send_address: String,
+    #[getset(get = "pub")]
+    overlay_background_color: String,
+    #[getset(get_copy = "pub")]
+    overlay_enabled: bool,
+    #[getset(get_copy = "pub")]
+    overlay_font_size: u32,
+    #[getset(get_copy = "pub")]
+    overlay_port: u16,
+    #[getset(get_copy = "pub")]
+    overlay_show_background: bool,
+    #[getset(get = "pub")]
+    overlay_text_color: String,
+    #[getset(get = "pub")]
+    overlay_winner_color: String,
     #[getset(get_copy = "pub")]
     reverse_chance: f64,
     speed_reduce_max: f64,
     speed_reduce_min: f64,
     #[getset(get_copy = "pub")]
+    sound_rotation: SoundRotation,
+    /// Send spectrum of the roll music with every wheel step, for visualisers
+    #[getset(get_copy = "pub")]
+    spectrum: bool,
+    #[getset(get_copy = "pub")]
     speed_slow_limit: f64,
     speed_slow_reduce_max: f64,
     speed_slow_reduce_min: f64,
@@ -36,17 +129,56 @@ pub struct Config {
     #[getset(get_copy = "pub")]
     start_fullscreen: bool,
     #[getset(get_copy = "pub")]
+    stinger_volume: u32,
+    /// Stop sound fade-in time in milliseconds
+    #[getset(get_copy = "pub")]
+    stop_sound_fade_in: u32,
+    /// Minimum time between two ticks in milliseconds, faster ticks are merged
+    #[getset(get_copy = "pub")]
+    tick_min_interval: u32,
+    /// Raise tick pitch with wheel speed
+    #[getset(get_copy = "pub")]
+    tick_pitch: bool,
+    #[getset(get_copy = "pub")]
+    tick_volume: u32,
+    #[getset(get_copy = "pub")]
+    ticks: bool,
+    #[getset(get_copy = "pub")]
     volume: u32,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            attract_delay: 0,
+            audio_cache_size: 256,
             audio_device: String::new(),
+            motion_mode: MotionMode::Random,
             music: true,
+            music_duck_volume: 30,
+            music_fade_out: 1000,
+            music_follow_speed: false,
+            music_random_start: false,
+            music_speed_max: 1.0,
+            music_speed_min: 0.5,
+            music_transition: MusicTransition::Sequential,
+            music_volume: 100,
+            normalize_loudness: false,
+            osc_enabled: false,
+            osc_listen_address: "127.0.0.1:9000".to_string(),
+            osc_send_address: String::new(),
+            overlay_background_color: "transparent".to_string(),
+            overlay_enabled: false,
+            overlay_font_size: 48,
+            overlay_port: 8790,
+            overlay_show_background: false,
+            overlay_text_color: "#ffffff".to_string(),
+            overlay_winner_color: "#ffdd57".to_string(),
             reverse_chance: 0.25,
             speed_reduce_max: 0.05,
             speed_reduce_min: 0.03,
+            sound_rotation: SoundRotation::ShuffleBag,
+            spectrum: false,
             speed_slow_limit: 1.0,
             speed_slow_reduce_max: 0.001,
             speed_slow_reduce_min: 0.01,
@@ -55,6 +187,12 @@ impl Default for Config {
             speed_stop_max: 0.5,
             speed_stop_min: 0.25,
             start_fullscreen: false,
+            stinger_volume: 100,
+            stop_sound_fade_in: 0,
+            tick_min_interval: 30,
+            tick_pitch: false,
+            tick_volume: 100,
+            ticks: true,
             volume: 100,
         }
     }
@@ -63,7 +201,7 @@ impl Default for Config {
 impl Config {
     pub(crate) fn load() -> Self {
         let config_path = config_path();
-        let mut result = if config_path.is_file() {
+        let result = if config_path.is_file() {
             match Config::read_config(config_path.as_path()) {
                 Ok(config) => config,
                 Err(e) => {
@@ -75,26 +213,35 @@ impl Config {
             Self::default()
         };
 
-        if result.speed_reduce_max < result.speed_reduce_min {
-            swap(&mut result.speed_reduce_max, &mut result.speed_reduce_min);
+        result.normalize()
+    }
+
+    /// Swaps minimum and maximum of the ranges that are set in reverse order
+    pub(crate) fn normalize(mut self) -> Self {
+        if self.music_speed_max < self.music_speed_min {
+            swap(&mut self.music_speed_max, &mut self.music_speed_min);
+        }
+
+        if self.speed_reduce_max < self.speed_reduce_min {
+            swap(&mut self.speed_reduce_max, &mut self.speed_reduce_min);
         }
 
-        if result.speed_slow_reduce_max < result.speed_slow_reduce_min {
+        if self.speed_slow_reduce_max < self.speed_slow_reduce_min {
             swap(
-                &mut result.speed_slow_reduce_max,
-                &mut result.speed_slow_reduce_min,
+                &mut self.speed_slow_reduce_max,
+                &mut self.speed_slow_reduce_min,
             );
         }
 
-        if result.speed_start_max < result.speed_start_min {
-            swap(&mut result.speed_start_max, &mut result.speed_start_min);
+        if self.speed_start_max < self.speed_start_min {
+            swap(&mut self.speed_start_max, &mut self.speed_start_min);
         }
 
-        if result.speed_stop_max < result.speed_stop_min {
-            swap(&mut result.speed_stop_max, &mut result.speed_stop_min);
+        if self.speed_stop_max < self.speed_stop_min {
+            swap(&mut self.speed_stop_max, &mut self.speed_stop_min);
         }
 
-        result
+        self
     }
 
     pub(crate) fn save(&self) -> Result<()> {
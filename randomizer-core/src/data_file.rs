@@ -0,0 +1,214 @@
+use std::path::{Component, Path, PathBuf};
+
+use log::warn;
+use percent_encoding::percent_decode_str;
+
+use crate::constants::DATA_PATH;
+
+/// Resolves a percent-encoded path relative to the data directory. Returns `None` if there is
+/// no such file or the path leads outside of the data directory, e.g. with `..` or a symlink
+pub fn data_file_path(path: &str) -> Option<PathBuf> {
+    resolve(&[DATA_PATH.as_path()], path)
+}
+
+/// Looks the path up in the roots in order, the first existing file wins
+fn resolve(roots: &[&Path], path: &str) -> Option<PathBuf> {
+    let relative = percent_decode_str(path).decode_utf8_lossy();
+    // Caught before touching the disk, so attempts are logged even for missing files
+    if escapes_root(Path::new(&*relative)) {
+        warn!("Rejected data file request {path:?}, it leads outside of the data directory");
+        return None;
+    }
+
+    for root in roots {
+        // Missing root or file, nothing to serve from it
+        let (root, file_path) = match (root.canonicalize(), root.join(&*relative).canonicalize()) {
+            (Ok(root), Ok(file_path)) => (root, file_path),
+            _ => continue,
+        };
+
+        // Canonical path has no `..` and symlinks resolved, so anything outside the root is an
+        // attempt to escape it
+        if !file_path.starts_with(&root) {
+            warn!(
+                "Rejected data file request {path:?}, it resolves to {} outside of {}",
+                file_path.display(),
+                root.display()
+            );
+            return None;
+        }
+
+        if file_path.is_file() {
+            return Some(file_path);
+        }
+    }
+
+    None
+}
+
+/// Returns `true` if the path is absolute or its `..` go above the directory it's relative to.
+/// Symlinks are not followed, `resolve` checks the canonical path for them
+fn escapes_root(path: &Path) -> bool {
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return true,
+            Component::CurDir => (),
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(x) => depth = x,
+                None => return true,
+            },
+            Component::Normal(_) => depth += 1,
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, write},
+        path::{Path, PathBuf},
+    };
+
+    use tempfile::TempDir;
+
+    use super::{escapes_root, resolve};
+
+    /// Temporary directory with a data root and a secret file next to it:
+    /// `secret.txt`, `data/bg/a b.png`
+    struct Fixture {
+        dir: TempDir,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let dir = TempDir::new().unwrap();
+            let base = dir.path();
+            create_dir_all(base.join("data").join("bg")).unwrap();
+            write(base.join("secret.txt"), "secret").unwrap();
+            write(base.join("data").join("bg").join("a b.png"), "png").unwrap();
+
+            Self { dir }
+        }
+
+        fn base(&self) -> &Path {
+            self.dir.path()
+        }
+
+        fn data(&self) -> PathBuf {
+            self.base().join("data")
+        }
+
+        fn resolve(&self, path: &str) -> Option<PathBuf> {
+            resolve(&[self.data().as_path()], path)
+        }
+    }
+
+    #[test]
+    fn resolves_files_inside_root() {
+        let fixture = Fixture::new();
+        let expected = fixture
+            .data()
+            .join("bg")
+            .join("a b.png")
+            .canonicalize()
+            .ok();
+
+        assert_eq!(fixture.resolve("bg/a%20b.png"), expected);
+        assert_eq!(fixture.resolve("bg%2Fa%20b.png"), expected);
+        assert_eq!(fixture.resolve("bg/../bg/a%20b.png"), expected);
+    }
+
+    #[test]
+    fn ignores_missing_files_and_directories() {
+        let fixture = Fixture::new();
+
+        assert_eq!(fixture.resolve("bg/missing.png"), None);
+        assert_eq!(fixture.resolve("bg"), None);
+        assert_eq!(fixture.resolve(""), None);
+    }
+
+    #[test]
+    fn rejects_encoded_traversal() {
+        let fixture = Fixture::new();
+
+        for path in [
+            "../secret.txt",
+            "..%2Fsecret.txt",
+            "%2E%2E%2Fsecret.txt",
+            "%2e%2e/secret.txt",
+            "bg/..%2F..%2Fsecret.txt",
+            "bg%2F%2E%2E%2F%2E%2E%2Fsecret.txt",
+        ] {
+            assert_eq!(fixture.resolve(path), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn detects_lexical_escapes() {
+        for path in [
+            "../secret.txt",
+            "bg/../../secret.txt",
+            "/etc/passwd",
+            "./..",
+        ] {
+            assert!(escapes_root(Path::new(path)), "{path}");
+        }
+        for path in ["bg/a.png", "bg/../a.png", "./bg/./a.png", "", "..a"] {
+            assert!(!escapes_root(Path::new(path)), "{path}");
+        }
+
+        let fixture = Fixture::new();
+        assert_eq!(fixture.resolve("..%2Fmissing.txt"), None);
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        let fixture = Fixture::new();
+        let secret = fixture
+            .base()
+            .join("secret.txt")
+            .to_string_lossy()
+            .to_string();
+        let encoded =
+            percent_encoding::utf8_percent_encode(&secret, percent_encoding::NON_ALPHANUMERIC)
+                .to_string();
+
+        assert_eq!(fixture.resolve(&secret), None);
+        assert_eq!(fixture.resolve(&encoded), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_escaping_root() {
+        use std::os::unix::fs::symlink;
+
+        let fixture = Fixture::new();
+        symlink(
+            fixture.base().join("secret.txt"),
+            fixture.data().join("link.txt"),
+        )
+        .unwrap();
+        symlink(fixture.base(), fixture.data().join("parent")).unwrap();
+        symlink(
+            fixture.data().join("bg").join("a b.png"),
+            fixture.data().join("inside.png"),
+        )
+        .unwrap();
+
+        assert_eq!(fixture.resolve("link.txt"), None);
+        assert_eq!(fixture.resolve("parent/secret.txt"), None);
+        assert_eq!(fixture.resolve("parent%2Fsecret.txt"), None);
+        assert_eq!(
+            fixture.resolve("inside.png"),
+            fixture
+                .data()
+                .join("bg")
+                .join("a b.png")
+                .canonicalize()
+                .ok()
+        );
+    }
+}
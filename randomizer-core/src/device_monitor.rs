@@ -0,0 +1,114 @@
+use std::{
+    sync::{Arc, Mutex, PoisonError, Weak},
+    thread,
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use tokio::sync::RwLock;
+
+use crate::{
+    audio_player::Player,
+    event::{Event, Events},
+};
+
+/// Time between device list checks
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Watches the audio device list. When the configured device disappears the output falls back
+/// to the default device, and it switches back once the device returns
+pub(crate) struct DeviceMonitor {
+    /// Driver of the configured device, empty for the default one
+    driver: Arc<Mutex<String>>,
+}
+
+impl DeviceMonitor {
+    /// Starts the monitor thread, it stops when the player is dropped
+    pub fn spawn(player: &Arc<RwLock<Player>>, events: Events, driver: &str) -> Self {
+        let driver = Arc::new(Mutex::new(driver.to_string()));
+        let task = MonitorTask {
+            devices: None,
+            driver: driver.clone(),
+            events,
+            fallback: false,
+            player: Arc::downgrade(player),
+        };
+        thread::spawn(move || task.run());
+
+        Self { driver }
+    }
+
+    /// Changes the configured device, the player is expected to switch to it already
+    pub fn set_driver(&self, driver: &str) {
+        *self.driver.lock().unwrap_or_else(PoisonError::into_inner) = driver.to_string();
+    }
+}
+
+struct MonitorTask {
+    /// Device list of the previous check
+    devices: Option<Vec<(String, String)>>,
+    driver: Arc<Mutex<String>>,
+    events: Events,
+    /// Output has been moved to the default device because the configured one is missing
+    fallback: bool,
+    player: Weak<RwLock<Player>>,
+}
+
+impl MonitorTask {
+    fn run(mut self) {
+        loop {
+            thread::sleep(POLL_INTERVAL);
+
+            let player = match self.player.upgrade() {
+                Some(player) => player,
+                None => break,
+            };
+            self.check(&player.blocking_read());
+        }
+    }
+
+    fn check(&mut self, player: &Player) {
+        let devices = match player.list_devices() {
+            Ok(devices) => devices,
+            Err(e) => {
+                error!("{:#?}", e);
+                return;
+            }
+        };
+
+        if self.devices.as_ref() != Some(&devices) {
+            // The first list is only remembered, the settings screen requests it on opening
+            if self.devices.is_some() {
+                self.events
+                    .emit(Event::AudioDevicesChanged(devices.clone()));
+            }
+            self.devices = Some(devices.clone());
+        }
+
+        // Nothing to switch to, e.g. when audio is unavailable
+        if devices.is_empty() {
+            return;
+        }
+
+        let driver = self
+            .driver
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let available = driver.is_empty() || devices.iter().any(|(x, _)| *x == driver);
+
+        if !available && !self.fallback {
+            warn!("Audio device {driver} has disappeared, switching to the default one");
+            match player.set_device("") {
+                Ok(()) => self.fallback = true,
+                Err(e) => error!("{:#?}", e),
+            }
+        } else if available && self.fallback {
+            info!("Audio device {driver} is available again, switching back to it");
+            match player.set_device(&driver) {
+                Ok(()) => self.fallback = false,
+                Err(e) => error!("{:#?}", e),
+            }
+        }
+    }
+}
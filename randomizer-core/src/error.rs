@@ -1,25 +1,75 @@
+use crate::audio_player::BassErrorCode;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[error("Invalid network address")]
+    AddrParse(#[from] std::net::AddrParseError),
+    #[error("Wheel is already rolling")]
+    AlreadyRolling,
     #[error("BASS error: {0} ({1})")]
-    Bass(String, String),
+    Bass(String, BassErrorCode),
+    #[error("BASS library is not available")]
+    BassUnavailable,
+    #[error("List {0} is empty")]
+    EmptyList(String),
     #[error("Source for C string contains NULL byte")]
     FfiNul(#[from] std::ffi::NulError),
+    #[error("Failed to process image")]
+    Image(#[from] image::ImageError),
     #[error("I/O error")]
     Io(#[from] std::io::Error),
     #[error("Failed to build logger configuration")]
     LoggerConfig(#[from] log4rs::config::runtime::ConfigErrors),
     #[error("Failed to initialize logger")]
     LoggerSet(#[from] log::SetLoggerError),
+    #[error("Failed to read audio file tags")]
+    Lofty(#[from] lofty::LoftyError),
+    #[error("OSC error")]
+    Osc(#[from] rosc::OscError),
+    #[cfg(feature = "rodio")]
+    #[error("Failed to decode audio file")]
+    RodioDecoder(#[from] rodio::decoder::DecoderError),
+    #[cfg(feature = "rodio")]
+    #[error("Failed to list audio devices")]
+    RodioDevices(#[from] rodio::DevicesError),
+    #[cfg(feature = "rodio")]
+    #[error("Failed to play audio")]
+    RodioPlay(#[from] rodio::PlayError),
+    #[cfg(feature = "rodio")]
+    #[error("Failed to open audio output")]
+    RodioStream(#[from] rodio::StreamError),
     #[error("Failed to strip path prefix")]
     StripPrefix(#[from] std::path::StripPrefixError),
     #[error("Failed to deserialize Toml")]
     TomlDeserialize(#[from] toml::de::Error),
     #[error("Failed to serialize Toml")]
     TomlSerialize(#[from] toml::ser::Error),
+    #[error("Unsupported playlist format: {0}")]
+    UnsupportedPlaylist(std::path::PathBuf),
     #[error("UTF-8 error")]
     Utf8(#[from] std::str::Utf8Error),
     #[error("Source for UTF-16 string contains NULL byte")]
     Utf16Nul(#[from] widestring::error::ContainsNul<u16>),
+    #[error("Failed to parse XML")]
+    Xml(#[from] roxmltree::Error),
+}
+
+impl Error {
+    /// Returns `true` if the error concerns a single file or playback attempt, so another track
+    /// may still play. Other errors mean audio doesn't work at all
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Error::Bass(_, code) => code.is_recoverable(),
+            Error::Io(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::PermissionDenied
+            ),
+            Error::Utf16Nul(_) => true,
+            #[cfg(feature = "rodio")]
+            Error::RodioDecoder(_) => true,
+            _ => false,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
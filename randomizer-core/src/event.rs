@@ -0,0 +1,104 @@
+use std::path::Path;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::{audio_player::TrackMetadata, playlist::PlaylistEntries, Config};
+
+const EVENT_CAPACITY: usize = 64;
+
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+pub enum Event {
+    /// Attract mode has started or stopped
+    Attract(bool),
+    /// Audio devices as (driver, name) pairs, sent when the list changes
+    AudioDevicesChanged(Vec<(String, String)>),
+    /// Reason why audio is unavailable, `None` when audio works again
+    AudioError(Option<String>),
+    Background(Option<String>),
+    Config(Box<Config>),
+    /// Roll music has faded out
+    FadeDone,
+    MusicStarted,
+    NowPlaying(Box<NowPlaying>),
+    Roll(String),
+    /// Music spectrum in frequency bands from low to high, in 0-1 range
+    Spectrum(Vec<f32>),
+    Stop,
+    /// Stop sound has played to the end
+    StopSoundEnded,
+    Stopping,
+    WheelList(Vec<String>),
+    Winner(String),
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Attract(_) => "attract",
+            Event::AudioDevicesChanged(_) => "audio-devices-changed",
+            Event::AudioError(_) => "audio-error",
+            Event::Background(_) => "background",
+            Event::Config(_) => "config",
+            Event::FadeDone => "fade-done",
+            Event::MusicStarted => "music-started",
+            Event::NowPlaying(_) => "now-playing",
+            Event::Roll(_) => "roll",
+            Event::Spectrum(_) => "spectrum",
+            Event::Stop => "stop",
+            Event::StopSoundEnded => "stop-sound-ended",
+            Event::Stopping => "stopping",
+            Event::WheelList(_) => "wheel-list",
+            Event::Winner(_) => "winner",
+        }
+    }
+}
+
+/// Sound that has started playing
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NowPlaying {
+    /// Sound category, `roll` for music, `stop` for stop sounds or `idle` for idle music
+    pub category: String,
+    #[serde(flatten)]
+    pub track: TrackMetadata,
+}
+
+impl NowPlaying {
+    pub(crate) fn event(category: &str, path: &Path, playlist_entries: &PlaylistEntries) -> Event {
+        Self::track_event(
+            category,
+            TrackMetadata::load(path, playlist_entries.get(path)),
+        )
+    }
+
+    /// Like `event`, but takes the metadata already read from the file
+    pub(crate) fn track_event(category: &str, track: TrackMetadata) -> Event {
+        Event::NowPlaying(Box::new(Self {
+            category: category.to_string(),
+            track,
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub struct Events {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Events {
+    pub(crate) fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn emit(&self, event: Event) {
+        // No subscribers is not an error, the event is simply dropped
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
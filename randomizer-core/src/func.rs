@@ -5,10 +5,15 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use log::{error, warn};
 use once_cell::sync::Lazy;
 use walkdir::{DirEntry, WalkDir};
 
-use crate::constants::DATA_PATH;
+use crate::{
+    constants::LIST_EXTENSION,
+    playlist::{Playlist, PlaylistEntries, PLAYLIST_EXTENSIONS},
+    Result,
+};
 
 static SOUND_EXTENSIONS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
     HashSet::from([
@@ -16,21 +21,35 @@ static SOUND_EXTENSIONS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
     ])
 });
 
-pub fn lines_from_file(path: impl AsRef<Path>) -> Vec<String> {
-    let file = File::open(path).expect("no such file");
-    let buf = BufReader::new(file);
-    buf.lines()
-        .map(|l| l.expect("Could not read line").trim().to_string())
-        .filter(|x| !x.starts_with('#'))
-        .collect()
+pub fn lines_from_file(path: impl AsRef<Path>) -> Result<Vec<String>> {
+    let buf = BufReader::new(File::open(path)?);
+    let mut result = vec![];
+    for line in buf.lines() {
+        let line = line?.trim().to_string();
+        if !line.starts_with('#') {
+            result.push(line);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Reads entries of the list from `lists/<list>.txt` in the data directory
+pub fn list_items(data_path: &Path, list_name: &str) -> Result<Vec<String>> {
+    let mut path = data_path.to_path_buf();
+    path.push("lists");
+    path.push(format!("{list_name}.{LIST_EXTENSION}"));
+
+    lines_from_file(path)
 }
 
 pub fn data_files_for_list(
+    data_path: &Path,
     base_dir: impl AsRef<Path>,
     list_name: &str,
     filter: fn(&DirEntry) -> bool,
 ) -> Vec<impl Iterator<Item = DirEntry>> {
-    let mut common_dir = DATA_PATH.clone();
+    let mut common_dir = data_path.to_path_buf();
     common_dir.push(base_dir);
 
     let mut list_dir = common_dir.clone();
@@ -63,12 +82,18 @@ pub fn files_from_dir(
         .filter(move |entry| entry.path().is_file() && filter(entry))
 }
 
-pub fn load_sound_lists(category: &str) -> HashMap<String, Vec<PathBuf>> {
-    let mut sounds_dir = DATA_PATH.clone();
+/// Loads sounds of the category by list, common sounds are under the empty name. Entries of
+/// the playlists the sounds come from are added to `playlist_entries`
+pub fn load_sound_lists(
+    data_path: &Path,
+    category: &str,
+    playlist_entries: &mut PlaylistEntries,
+) -> HashMap<String, Vec<PathBuf>> {
+    let mut sounds_dir = data_path.to_path_buf();
     sounds_dir.push("sounds");
     sounds_dir.push(category);
 
-    let mut result = HashMap::from([(String::new(), load_tracks(&sounds_dir))]);
+    let mut result = HashMap::from([(String::new(), load_tracks(&sounds_dir, playlist_entries))]);
 
     for entry in WalkDir::new(&sounds_dir)
         .follow_links(false)
@@ -79,29 +104,49 @@ pub fn load_sound_lists(category: &str) -> HashMap<String, Vec<PathBuf>> {
     {
         result.insert(
             entry.file_name().to_string_lossy().to_string(),
-            load_tracks(&entry.path().to_path_buf()),
+            load_tracks(&entry.path().to_path_buf(), playlist_entries),
         );
     }
 
     result
 }
 
-fn load_tracks(base_dir: &PathBuf) -> Vec<PathBuf> {
-    let mut result: Vec<PathBuf> = files_from_dir(base_dir, |file| {
+/// Returns audio files of the directory, playlists are not read
+pub fn sound_files_from_dir(dir: &PathBuf) -> impl Iterator<Item = PathBuf> {
+    files_from_dir(dir, |file| {
         SOUND_EXTENSIONS.contains(&dir_entry_extension(file).as_str())
     })
     .map(|x| x.path().to_path_buf())
-    .collect();
-
-    let mut playlist_path = base_dir.clone();
-    playlist_path.push("!playlist.m3u8");
-    if playlist_path.is_file() {
-        result.extend(
-            lines_from_file(playlist_path)
-                .iter()
-                .map(PathBuf::from)
-                .filter(|x| x.is_file()),
-        );
+}
+
+fn load_tracks(base_dir: &PathBuf, playlist_entries: &mut PlaylistEntries) -> Vec<PathBuf> {
+    let mut result: Vec<PathBuf> = sound_files_from_dir(base_dir).collect();
+
+    for extension in PLAYLIST_EXTENSIONS {
+        let mut playlist_path = base_dir.clone();
+        playlist_path.push(format!("!playlist.{extension}"));
+        if !playlist_path.is_file() {
+            continue;
+        }
+
+        match Playlist::load(&playlist_path) {
+            Ok(playlist) => {
+                for location in playlist.missing {
+                    warn!(
+                        "Playlist {} refers to missing file {}",
+                        playlist_path.display(),
+                        location
+                    );
+                }
+                result.extend(playlist.entries.iter().map(|x| x.path.clone()));
+                playlist_entries.extend(playlist.entries.into_iter().map(|x| (x.path.clone(), x)));
+            }
+            Err(e) => error!(
+                "Failed to read playlist {}: {:#?}",
+                playlist_path.display(),
+                e
+            ),
+        }
     }
 
     result
@@ -0,0 +1,378 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    fs::{create_dir_all, metadata, read_dir, remove_file, rename, File, Metadata},
+    hash::{Hash, Hasher},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+    thread,
+    time::UNIX_EPOCH,
+};
+
+use image::{imageops::FilterType, ImageFormat, ImageOutputFormat};
+use log::{error, info};
+use once_cell::sync::Lazy;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use walkdir::WalkDir;
+
+use crate::{constants::CONFIG_PATH, Result};
+
+const CACHE_DIR: &str = "image-cache";
+/// Widest variant that is generated, bigger requests get the original image
+const MAX_WIDTH: u32 = 7680;
+const JPEG_QUALITY: u8 = 90;
+
+/// Work for the thread that makes and prunes variants, one job at a time. Jobs carry the cache
+/// directory they work on
+enum Job {
+    Prune(PathBuf, PathBuf),
+    Resize(PathBuf, PathBuf, u32),
+}
+
+static WORKER: Lazy<UnboundedSender<Job>> = Lazy::new(spawn_worker);
+/// Variants that are queued or being made, so every one is made once
+static PENDING: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(Default::default);
+
+/// Returns a variant of the image scaled down to `width`, keeping the aspect ratio. Variants
+/// are made in the background and cached in the config directory, the source itself is
+/// returned until the variant is ready. Variants are made again when the source changes. The
+/// source is also returned if it's not wider than `width` or its format can't be resized
+pub fn resized_image(path: &Path, width: u32) -> Result<PathBuf> {
+    request_variant(&cache_dir(), path, width)
+}
+
+/// Removes variants of images that were deleted or replaced in the data directory since the
+/// variants were made. Runs in the background after the queued variants
+pub(crate) fn prune_image_cache(data_path: &Path) {
+    queue(Job::Prune(cache_dir(), data_path.to_path_buf()));
+}
+
+fn cache_dir() -> PathBuf {
+    CONFIG_PATH.join(CACHE_DIR)
+}
+
+fn request_variant(cache_dir: &Path, path: &Path, width: u32) -> Result<PathBuf> {
+    let variant_path = match variant_path(cache_dir, path, width)? {
+        Some(variant_path) => variant_path,
+        None => return Ok(path.to_path_buf()),
+    };
+    if variant_path.is_file() {
+        return Ok(variant_path);
+    }
+
+    if pending().insert(variant_path.clone()) {
+        queue(Job::Resize(
+            cache_dir.to_path_buf(),
+            path.to_path_buf(),
+            width,
+        ));
+    }
+
+    Ok(path.to_path_buf())
+}
+
+fn pending() -> MutexGuard<'static, HashSet<PathBuf>> {
+    PENDING.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn queue(job: Job) {
+    if WORKER.send(job).is_err() {
+        error!("Image cache worker has stopped");
+    }
+}
+
+fn spawn_worker() -> UnboundedSender<Job> {
+    let (sender, mut receiver) = unbounded_channel();
+    thread::spawn(move || {
+        while let Some(job) = receiver.blocking_recv() {
+            match job {
+                Job::Prune(cache_dir, data_path) => {
+                    if let Err(e) = prune(&cache_dir, &data_path) {
+                        error!("Failed to prune image cache: {:#?}", e);
+                    }
+                }
+                // A failed variant stays pending, so it isn't tried again until the source
+                // changes or the app restarts
+                Job::Resize(cache_dir, path, width) => {
+                    match resize_into(&cache_dir, &path, width) {
+                        Ok(variant_path) => {
+                            pending().remove(&variant_path);
+                        }
+                        Err(e) => error!("Failed to resize {}: {:#?}", path.display(), e),
+                    }
+                }
+            }
+        }
+    });
+
+    sender
+}
+
+/// Returns path of the variant of the image at `width`, `None` if the image is served as it is
+fn variant_path(cache_dir: &Path, path: &Path, width: u32) -> Result<Option<PathBuf>> {
+    if width == 0 || width > MAX_WIDTH {
+        return Ok(None);
+    }
+    let format = match resizable_format(path) {
+        Some(format) => format,
+        None => return Ok(None),
+    };
+
+    let (source_width, _) = image::image_dimensions(path)?;
+    if source_width <= width {
+        return Ok(None);
+    }
+
+    let key = source_key(path, &metadata(path)?);
+    let extension = variant_format(format).extensions_str()[0];
+    Ok(Some(cache_dir.join(format!("{key}-{width}.{extension}"))))
+}
+
+/// Makes the variant of the image at `width` unless it's cached and returns its path, or the
+/// source itself if it's served as it is
+fn resize_into(cache_dir: &Path, path: &Path, width: u32) -> Result<PathBuf> {
+    let cache_path = match variant_path(cache_dir, path, width)? {
+        Some(cache_path) => cache_path,
+        None => return Ok(path.to_path_buf()),
+    };
+    if cache_path.is_file() {
+        return Ok(cache_path);
+    }
+
+    let original = image::open(path)?;
+    // At least a pixel high for extremely wide images
+    let height = ((u64::from(original.height()) * u64::from(width) / u64::from(original.width()))
+        as u32)
+        .max(1);
+    info!(
+        "Resizing {} from {}x{} to {width}x{height}",
+        path.display(),
+        original.width(),
+        original.height()
+    );
+
+    create_dir_all(cache_dir)?;
+    // Written under another name first, so a half-written variant is never served
+    let temp_path = cache_path.with_extension("tmp");
+    let output_format = match ImageFormat::from_path(&cache_path)? {
+        ImageFormat::Jpeg => ImageOutputFormat::Jpeg(JPEG_QUALITY),
+        _ => ImageOutputFormat::Png,
+    };
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    original
+        .resize_exact(width, height, FilterType::CatmullRom)
+        .write_to(&mut writer, output_format)?;
+    writer.flush()?;
+    rename(temp_path, &cache_path)?;
+
+    // Variants of an older version of the file are never served again
+    let key = source_key(path, &metadata(path)?);
+    let (source, _) = key.split_once('-').unwrap_or_default();
+    remove_variants(cache_dir, |variant_key| {
+        variant_key != key && variant_key.starts_with(source)
+    })?;
+
+    Ok(cache_path)
+}
+
+fn prune(cache_dir: &Path, data_path: &Path) -> Result<()> {
+    if !cache_dir.is_dir() {
+        return Ok(());
+    }
+
+    // Variants are made for canonical paths, `data_file_path` resolves them
+    let keys: HashSet<String> = WalkDir::new(data_path)
+        .into_iter()
+        .filter_map(std::result::Result::ok)
+        .filter(|entry| entry.file_type().is_file() && resizable_format(entry.path()).is_some())
+        .filter_map(|entry| {
+            let path = entry.path().canonicalize().ok()?;
+            let metadata = metadata(&path).ok()?;
+            Some(source_key(&path, &metadata))
+        })
+        .collect();
+
+    remove_variants(cache_dir, |key| !keys.contains(key))
+}
+
+/// Removes variants whose source key matches. Variants are named
+/// `<source key>-<width>.<extension>`
+fn remove_variants(cache_dir: &Path, matches: impl Fn(&str) -> bool) -> Result<()> {
+    for entry in read_dir(cache_dir)? {
+        let path = entry?.path();
+        let name = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let key = name.rsplit_once('-').map_or(name.as_str(), |(key, _)| key);
+        if path.is_file() && matches(key) {
+            info!("Removing stale image variant {}", path.display());
+            remove_file(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn resizable_format(path: &Path) -> Option<ImageFormat> {
+    ImageFormat::from_path(path).ok().filter(|format| {
+        matches!(
+            format,
+            ImageFormat::Bmp | ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP
+        )
+    })
+}
+
+/// Format of the variants of a source format: JPEG stays JPEG, others become PNG, which keeps
+/// them lossless and transparent
+fn variant_format(format: ImageFormat) -> ImageFormat {
+    match format {
+        ImageFormat::Jpeg => ImageFormat::Jpeg,
+        _ => ImageFormat::Png,
+    }
+}
+
+/// Names the variants of a file: a hash of the source path and a hash of its size and
+/// modification time, so a replaced or edited file gets new variants
+fn source_key(path: &Path, metadata: &Metadata) -> String {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    let source = hasher.finish();
+
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    let mut hasher = DefaultHasher::new();
+    (metadata.len(), modified).hash(&mut hasher);
+
+    format!("{source:016x}-{:016x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, read_dir, remove_file, write},
+        path::{Path, PathBuf},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use image::{ImageFormat, RgbImage};
+    use tempfile::TempDir;
+
+    use super::{prune, request_variant, resize_into};
+
+    /// Temporary directory with data and cache directories
+    struct Fixture {
+        dir: TempDir,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let dir = TempDir::new().unwrap();
+            let base = dir.path();
+            create_dir_all(base.join("data")).unwrap();
+
+            Self { dir }
+        }
+
+        fn base(&self) -> &Path {
+            self.dir.path()
+        }
+
+        fn cache(&self) -> PathBuf {
+            self.base().join("cache")
+        }
+
+        /// Writes an image of the given size, the format follows the extension
+        fn image(&self, file_name: &str, width: u32, height: u32) -> PathBuf {
+            let path = self.base().join("data").join(file_name);
+            RgbImage::new(width, height).save(&path).unwrap();
+            path.canonicalize().unwrap()
+        }
+
+        fn variants(&self) -> usize {
+            read_dir(self.cache()).map_or(0, |x| x.count())
+        }
+    }
+
+    #[test]
+    fn resizes_png_and_jpeg() {
+        let fixture = Fixture::new();
+        for (file_name, format) in [("a.png", ImageFormat::Png), ("b.jpg", ImageFormat::Jpeg)] {
+            let source = fixture.image(file_name, 40, 20);
+            let variant = resize_into(&fixture.cache(), &source, 10).unwrap();
+
+            assert!(variant.starts_with(fixture.cache()));
+            assert_eq!(image::image_dimensions(&variant).unwrap(), (10, 5));
+            assert_eq!(ImageFormat::from_path(&variant).unwrap(), format);
+            assert_eq!(resize_into(&fixture.cache(), &source, 10).unwrap(), variant);
+        }
+        assert_eq!(fixture.variants(), 2);
+
+        // Small images and unknown formats are served as they are
+        let source = fixture.image("c.png", 8, 8);
+        assert_eq!(resize_into(&fixture.cache(), &source, 10).unwrap(), source);
+        let source = fixture.base().join("data").join("d.gif");
+        write(&source, "GIF89a").unwrap();
+        assert_eq!(resize_into(&fixture.cache(), &source, 10).unwrap(), source);
+        assert_eq!(fixture.variants(), 2);
+    }
+
+    #[test]
+    fn serves_source_until_variant_is_ready() {
+        let fixture = Fixture::new();
+        let source = fixture.image("a.png", 40, 20);
+        assert_eq!(
+            request_variant(&fixture.cache(), &source, 10).unwrap(),
+            source
+        );
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let variant = loop {
+            let path = request_variant(&fixture.cache(), &source, 10).unwrap();
+            if path != source {
+                break path;
+            }
+            assert!(Instant::now() < deadline, "variant wasn't made");
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        assert_eq!(image::image_dimensions(variant).unwrap(), (10, 5));
+        assert_eq!(fixture.variants(), 1);
+    }
+
+    #[test]
+    fn replaces_variants_of_changed_files() {
+        let fixture = Fixture::new();
+        let source = fixture.image("a.png", 40, 20);
+        let old = resize_into(&fixture.cache(), &source, 10).unwrap();
+
+        // Another size changes the key even if the modification time doesn't
+        fixture.image("a.png", 40, 40);
+        let new = resize_into(&fixture.cache(), &source, 10).unwrap();
+
+        assert_ne!(old, new);
+        assert!(!old.exists());
+        assert_eq!(image::image_dimensions(&new).unwrap(), (10, 10));
+        assert_eq!(fixture.variants(), 1);
+    }
+
+    #[test]
+    fn prunes_variants_of_deleted_files() {
+        let fixture = Fixture::new();
+        let kept = fixture.image("a.png", 40, 20);
+        let deleted = fixture.image("b.png", 40, 20);
+        let kept = resize_into(&fixture.cache(), &kept, 10).unwrap();
+        resize_into(&fixture.cache(), &deleted, 10).unwrap();
+        remove_file(deleted).unwrap();
+
+        prune(&fixture.cache(), &fixture.base().join("data")).unwrap();
+
+        assert!(kept.exists());
+        assert_eq!(fixture.variants(), 1);
+    }
+}
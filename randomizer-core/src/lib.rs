@@ -1,40 +1,51 @@
+mod attract;
 mod audio_player;
+mod background;
 mod config;
 mod constants;
+mod data_file;
+mod device_monitor;
 mod error;
+mod event;
 mod func;
+mod image_cache;
+mod list_info;
+mod osc;
+mod playlist;
 mod randomizer;
+mod rotation;
+mod sequencer;
+mod track_info;
 
-use std::{collections::HashSet, path::PathBuf};
+use std::path::PathBuf;
 
 use constants::{DATA_PATH, LIST_EXTENSION};
-use func::{data_files_for_list, dir_entry_extension};
-use once_cell::sync::Lazy;
-use rand::seq::IteratorRandom;
 use walkdir::WalkDir;
 
-use crate::audio_player::Player;
-
 pub use crate::{
-    config::Config,
+    audio_player::{
+        AudioBackend, AudioCall, AudioDecoder, AudioSample, AudioStream, BassBackend,
+        BassErrorCode, Channel, LoopSection, NullBackend, SyncCallback, SyncType, TrackMetadata,
+    },
+    config::{Config, MotionMode, MusicTransition, SoundRotation},
+    data_file::data_file_path,
     error::{Error, Result},
-    randomizer::Randomizer,
+    event::{Event, Events, NowPlaying},
+    image_cache::resized_image,
+    osc::{OscCommand, OscSocket},
+    playlist::{Playlist, PlaylistEntry},
+    randomizer::{Randomizer, SoundLibrary},
 };
 
-static BG_EXTENSIONS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
-    HashSet::from([
-        "bmp", "gif", "jpg", "jpeg", "png", "svg", "webp", "mp4", "webm",
-    ])
-});
+#[cfg(feature = "rodio")]
+pub use crate::audio_player::RodioBackend;
 
 pub fn data_path() -> PathBuf {
     DATA_PATH.clone()
 }
 
-pub fn list_audio_devices() -> Result<Vec<(String, String)>> {
-    Player::list_devices()
-}
-
+// Baseline code from before `is_some_and`
+#[allow(clippy::unnecessary_map_or)]
 pub fn lists() -> Vec<String> {
     let mut lists_dir = DATA_PATH.clone();
     lists_dir.push("lists");
@@ -59,24 +70,3 @@ pub fn lists() -> Vec<String> {
 
     lists
 }
-
-pub fn random_bg(list_name: &str) -> Result<Option<String>> {
-    let mut bg_dir = DATA_PATH.clone();
-    bg_dir.push("bg");
-    bg_dir.push(list_name);
-
-    let mut rng = rand::thread_rng();
-
-    for dir in data_files_for_list("bg", list_name, |file| {
-        BG_EXTENSIONS.contains(&dir_entry_extension(file).as_str())
-    }) {
-        if let Some(entry) = dir.choose(&mut rng) {
-            let file_path = entry.path().to_path_buf();
-            let file_path = file_path.strip_prefix(DATA_PATH.clone())?;
-
-            return Ok(Some(file_path.to_string_lossy().to_string()));
-        }
-    }
-
-    Ok(None)
-}
@@ -0,0 +1,72 @@
+use std::{
+    collections::HashMap,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use log::error;
+use serde::Deserialize;
+
+use crate::{config::SoundRotation, Result};
+
+const SIDECAR_EXTENSION: &str = "toml";
+
+/// Metadata of a list entry
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub(crate) struct EntryInfo {
+    /// Sound played when the entry wins, relative to `data/sounds/stop`
+    pub stop_sound: Option<PathBuf>,
+}
+
+/// Background settings of a list
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub(crate) struct BackgroundInfo {
+    /// How the next background is picked
+    pub rotation: SoundRotation,
+    /// Seconds between background changes during a roll, zero keeps one background
+    pub slideshow: u32,
+    /// Show own background of the winner, a file named after the entry in `data/bg/<list>`
+    pub winner: bool,
+}
+
+impl Default for BackgroundInfo {
+    fn default() -> Self {
+        Self {
+            rotation: SoundRotation::ShuffleBag,
+            slideshow: 0,
+            winner: true,
+        }
+    }
+}
+
+/// Metadata of the list and its entries, read from a sidecar file next to the list (`lists/<list>.toml`)
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub(crate) struct ListInfo {
+    pub background: BackgroundInfo,
+    /// Entry metadata by entry text
+    pub entries: HashMap<String, EntryInfo>,
+}
+
+impl ListInfo {
+    /// Reads metadata of the list, missing sidecar file means no metadata
+    pub fn load(data_path: &Path, list_name: &str) -> Self {
+        let mut path = data_path.to_path_buf();
+        path.push("lists");
+        path.push(format!("{list_name}.{SIDECAR_EXTENSION}"));
+        if !path.is_file() {
+            return Self::default();
+        }
+
+        Self::read(&path).unwrap_or_else(|e| {
+            error!("Failed to read list metadata {}: {:#?}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        Ok(toml::from_str(read_to_string(path)?.as_str())?)
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    io::ErrorKind,
+    net::{SocketAddr, UdpSocket},
+    time::Duration,
+};
+
+use log::warn;
+use rosc::{OscMessage, OscPacket, OscType};
+
+use crate::{Event, Result};
+
+const ADDRESS_PREFIX: &str = "/randomizer";
+const BUFFER_SIZE: usize = rosc::decoder::MTU;
+const READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OscCommand {
+    Roll(String),
+    Stop,
+    Volume(u32),
+}
+
+/// UDP socket that receives control messages and sends state changes as OSC
+pub struct OscSocket {
+    send_to: Option<SocketAddr>,
+    socket: UdpSocket,
+}
+
+impl OscSocket {
+    /// Binds to `listen_address`, events are sent to `send_to` if it is not empty
+    pub fn bind(listen_address: &str, send_to: &str) -> Result<Self> {
+        let socket = UdpSocket::bind(listen_address)?;
+        socket.set_read_timeout(Some(READ_TIMEOUT))?;
+
+        let send_to = if send_to.is_empty() {
+            None
+        } else {
+            Some(send_to.parse()?)
+        };
+
+        Ok(Self { send_to, socket })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Waits for the next packet, returns no commands if nothing was received before timeout
+    pub fn receive(&self) -> Result<Vec<OscCommand>> {
+        let mut buffer = [0u8; BUFFER_SIZE];
+        let size = match self.socket.recv_from(&mut buffer) {
+            Ok((size, _)) => size,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return Ok(vec![]);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let (_, packet) = rosc::decoder::decode_udp(&buffer[..size])?;
+        let mut result = vec![];
+        collect_commands(packet, &mut result);
+
+        Ok(result)
+    }
+
+    pub fn send_event(&self, event: &Event) -> Result<()> {
+        if let (Some(send_to), Some(message)) = (self.send_to, event_message(event)) {
+            self.socket.send_to(
+                &rosc::encoder::encode(&OscPacket::Message(message))?,
+                send_to,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn collect_commands(packet: OscPacket, commands: &mut Vec<OscCommand>) {
+    match packet {
+        OscPacket::Message(message) => match parse_message(&message) {
+            Some(command) => commands.push(command),
+            None => warn!("Unsupported OSC message: {:?}", message),
+        },
+        OscPacket::Bundle(bundle) => {
+            for packet in bundle.content {
+                collect_commands(packet, commands);
+            }
+        }
+    }
+}
+
+fn parse_message(message: &OscMessage) -> Option<OscCommand> {
+    let command = message.addr.strip_prefix(ADDRESS_PREFIX)?;
+    let argument = message.args.first();
+
+    match command {
+        "/roll" => match argument {
+            Some(OscType::String(list_name)) => Some(OscCommand::Roll(list_name.clone())),
+            _ => None,
+        },
+        "/stop" => Some(OscCommand::Stop),
+        "/volume" => {
+            let volume = match argument? {
+                OscType::Int(value) => f64::from(*value),
+                OscType::Long(value) => *value as f64,
+                OscType::Float(value) => f64::from(*value),
+                OscType::Double(value) => *value,
+                _ => return None,
+            };
+            Some(OscCommand::Volume(volume.round().clamp(0.0, 100.0) as u32))
+        }
+        _ => None,
+    }
+}
+
+fn event_message(event: &Event) -> Option<OscMessage> {
+    let (command, args) = match event {
+        Event::Roll(list_name) => (
+            "/state",
+            vec![
+                OscType::String("rolling".to_string()),
+                OscType::String(list_name.clone()),
+            ],
+        ),
+        Event::Stopping => ("/state", vec![OscType::String("stopping".to_string())]),
+        Event::Stop => ("/state", vec![OscType::String("stopped".to_string())]),
+        Event::Winner(name) => ("/winner", vec![OscType::String(name.clone())]),
+        _ => return None,
+    };
+
+    Some(OscMessage {
+        addr: format!("{ADDRESS_PREFIX}{command}"),
+        args,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+
+    use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+
+    use super::{OscCommand, OscSocket, BUFFER_SIZE, READ_TIMEOUT};
+    use crate::Event;
+
+    /// Randomizer socket and a controller socket that talk over loopback
+    fn sockets() -> (OscSocket, UdpSocket) {
+        let controller = UdpSocket::bind("127.0.0.1:0").unwrap();
+        controller.set_read_timeout(Some(READ_TIMEOUT)).unwrap();
+        let socket =
+            OscSocket::bind("127.0.0.1:0", &controller.local_addr().unwrap().to_string()).unwrap();
+
+        (socket, controller)
+    }
+
+    fn message(addr: &str, args: Vec<OscType>) -> OscPacket {
+        OscPacket::Message(OscMessage {
+            addr: addr.to_string(),
+            args,
+        })
+    }
+
+    fn send(controller: &UdpSocket, socket: &OscSocket, packet: &OscPacket) {
+        controller
+            .send_to(
+                &rosc::encoder::encode(packet).unwrap(),
+                socket.local_addr().unwrap(),
+            )
+            .unwrap();
+    }
+
+    fn receive(controller: &UdpSocket) -> Option<OscPacket> {
+        let mut buffer = [0u8; BUFFER_SIZE];
+        let (size, _) = controller.recv_from(&mut buffer).ok()?;
+        Some(rosc::decoder::decode_udp(&buffer[..size]).unwrap().1)
+    }
+
+    #[test]
+    fn dispatches_messages() {
+        let (socket, controller) = sockets();
+
+        for (packet, expected) in [
+            (
+                message("/randomizer/roll", vec![OscType::String("games".into())]),
+                vec![OscCommand::Roll("games".to_string())],
+            ),
+            (message("/randomizer/stop", vec![]), vec![OscCommand::Stop]),
+            (
+                message("/randomizer/volume", vec![OscType::Float(55.6)]),
+                vec![OscCommand::Volume(56)],
+            ),
+            (
+                message("/randomizer/volume", vec![OscType::Int(150)]),
+                vec![OscCommand::Volume(100)],
+            ),
+            (message("/randomizer/roll", vec![OscType::Int(1)]), vec![]),
+            (message("/other/stop", vec![]), vec![]),
+            (
+                OscPacket::Bundle(OscBundle {
+                    timetag: OscTime::from((0, 1)),
+                    content: vec![
+                        message("/randomizer/stop", vec![]),
+                        message("/randomizer/volume", vec![OscType::Double(-5.0)]),
+                    ],
+                }),
+                vec![OscCommand::Stop, OscCommand::Volume(0)],
+            ),
+        ] {
+            send(&controller, &socket, &packet);
+            assert_eq!(socket.receive().unwrap(), expected, "{packet:?}");
+        }
+    }
+
+    #[test]
+    fn receive_times_out_without_packets() {
+        let (socket, _controller) = sockets();
+
+        assert_eq!(socket.receive().unwrap(), vec![]);
+    }
+
+    #[test]
+    fn sends_events() {
+        let (socket, controller) = sockets();
+
+        socket
+            .send_event(&Event::Roll("games".to_string()))
+            .unwrap();
+        assert_eq!(
+            receive(&controller),
+            Some(message(
+                "/randomizer/state",
+                vec![
+                    OscType::String("rolling".to_string()),
+                    OscType::String("games".to_string())
+                ]
+            ))
+        );
+
+        socket
+            .send_event(&Event::Winner("Tetris".to_string()))
+            .unwrap();
+        assert_eq!(
+            receive(&controller),
+            Some(message(
+                "/randomizer/winner",
+                vec![OscType::String("Tetris".to_string())]
+            ))
+        );
+
+        // Events without an OSC message are not sent
+        socket.send_event(&Event::MusicStarted).unwrap();
+        assert_eq!(receive(&controller), None);
+    }
+
+    #[test]
+    fn sends_nothing_without_feedback_address() {
+        let socket = OscSocket::bind("127.0.0.1:0", "").unwrap();
+
+        socket.send_event(&Event::Stop).unwrap();
+    }
+}
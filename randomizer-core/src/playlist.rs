@@ -0,0 +1,366 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::read,
+    path::{Path, PathBuf},
+};
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::{Error, Result};
+
+/// Track of a playlist with metadata stored in the playlist itself
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlaylistEntry {
+    /// Duration in seconds
+    pub duration: Option<f64>,
+    pub path: PathBuf,
+    pub title: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Playlist {
+    /// Entries that point to existing files
+    pub entries: Vec<PlaylistEntry>,
+    /// Entries that point to missing files or can't be resolved to a file, as written in the
+    /// playlist
+    pub missing: Vec<String>,
+}
+
+impl Playlist {
+    /// Reads M3U, PLS or XSPF playlist, the format is chosen by the file extension. Relative paths
+    /// are resolved against the playlist directory.
+    pub fn load(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        let bytes = read(path)?;
+        // Legacy playlists may be in a local code page, such entries are reported as missing
+        let content = String::from_utf8_lossy(&bytes);
+        let content = content.trim_start_matches('\u{feff}');
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let entries = match extension.as_str() {
+            "m3u" | "m3u8" => parse_m3u(content),
+            "pls" => parse_pls(content),
+            "xspf" => parse_xspf(content)?,
+            _ => return Err(Error::UnsupportedPlaylist(path.to_path_buf())),
+        };
+
+        let mut result = Self::default();
+        for (location, duration, title) in entries {
+            match resolve(base_dir, &location).filter(|x| x.is_file()) {
+                Some(path) => result.entries.push(PlaylistEntry {
+                    duration,
+                    path,
+                    title,
+                }),
+                None => result.missing.push(location),
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+pub(crate) const PLAYLIST_EXTENSIONS: [&str; 4] = ["m3u", "m3u8", "pls", "xspf"];
+
+/// Entries of the playlists that sounds were loaded from, by path, so their titles and
+/// durations show up in the track metadata
+pub(crate) type PlaylistEntries = HashMap<PathBuf, PlaylistEntry>;
+
+/// Location as written in the playlist, duration and title
+type RawEntry = (String, Option<f64>, Option<String>);
+
+fn parse_m3u(content: &str) -> Vec<RawEntry> {
+    let mut result = vec![];
+    let mut info = None;
+
+    for line in content.lines().map(str::trim).filter(|x| !x.is_empty()) {
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            // #EXTINF:<seconds>[ attributes],<title>
+            let (duration, title) = extinf.split_once(',').unwrap_or((extinf, ""));
+            let duration = duration.split_whitespace().next().and_then(parse_duration);
+            let title = Some(title.trim().to_string()).filter(|x| !x.is_empty());
+            info = Some((duration, title));
+        } else if !line.starts_with('#') {
+            let (duration, title) = info.take().unwrap_or_default();
+            result.push((line.to_string(), duration, title));
+        }
+    }
+
+    result
+}
+
+fn parse_pls(content: &str) -> Vec<RawEntry> {
+    let mut entries: BTreeMap<u32, RawEntry> = BTreeMap::new();
+
+    for line in content.lines().map(str::trim) {
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+            None => continue,
+        };
+
+        // Keys are File<N>, Title<N> and Length<N>, numbered from 1
+        let index_of = |prefix: &str| key.strip_prefix(prefix).and_then(|x| x.parse::<u32>().ok());
+        if let Some(index) = index_of("file") {
+            entries.entry(index).or_default().0 = value.to_string();
+        } else if let Some(index) = index_of("title") {
+            entries.entry(index).or_default().2 = Some(value.to_string()).filter(|x| !x.is_empty());
+        } else if let Some(index) = index_of("length") {
+            entries.entry(index).or_default().1 = parse_duration(value);
+        }
+    }
+
+    entries
+        .into_values()
+        .filter(|(location, _, _)| !location.is_empty())
+        .collect()
+}
+
+fn parse_xspf(content: &str) -> Result<Vec<RawEntry>> {
+    let document = roxmltree::Document::parse(content)?;
+
+    Ok(document
+        .descendants()
+        .filter(|node| node.has_tag_name("track"))
+        .filter_map(|track| {
+            let child_text = |name: &str| {
+                track
+                    .children()
+                    .find(|node| node.has_tag_name(name))
+                    .and_then(|node| node.text())
+                    .map(|text| text.trim().to_string())
+            };
+
+            let location = child_text("location")?;
+            // XSPF durations are in milliseconds
+            let duration = child_text("duration")
+                .and_then(|x| x.parse::<f64>().ok())
+                .map(|x| x / 1000.0);
+            let title = child_text("title").filter(|x| !x.is_empty());
+
+            Some((location, duration, title))
+        })
+        .collect())
+}
+
+/// Negative duration means unknown length in M3U and PLS
+fn parse_duration(value: &str) -> Option<f64> {
+    value.parse::<f64>().ok().filter(|x| *x >= 0.0)
+}
+
+fn resolve(base_dir: &Path, location: &str) -> Option<PathBuf> {
+    if location.contains("://") {
+        // Only local files can be played, other URLs are reported as missing
+        return Url::parse(location).ok()?.to_file_path().ok();
+    }
+
+    let result = base_dir.join(location);
+    if result.exists() {
+        return Some(result);
+    }
+
+    // XSPF locations are relative URIs, so they may be percent-encoded
+    let decoded = percent_decode_str(location).decode_utf8().ok()?;
+    Some(base_dir.join(decoded.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, write},
+        path::{Path, PathBuf},
+    };
+
+    use tempfile::TempDir;
+    use url::Url;
+
+    use super::{parse_m3u, parse_pls, parse_xspf, Playlist, PlaylistEntries, PlaylistEntry};
+    use crate::{audio_player::TrackMetadata, Error};
+
+    /// Temporary directory with sounds: `a.mp3`, `b c.mp3`, `sub/d.ogg`
+    struct Fixture {
+        dir: TempDir,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let dir = TempDir::new().unwrap();
+            let base = dir.path();
+            create_dir_all(base.join("sub")).unwrap();
+            for file in ["a.mp3", "b c.mp3", "sub/d.ogg"] {
+                write(base.join(file), "").unwrap();
+            }
+
+            Self { dir }
+        }
+
+        fn base(&self) -> &Path {
+            self.dir.path()
+        }
+
+        fn load(&self, file_name: &str, content: &str) -> Playlist {
+            let path = self.base().join(file_name);
+            write(&path, content).unwrap();
+            Playlist::load(&path).unwrap()
+        }
+
+        fn entry(&self, file: &str, duration: Option<f64>, title: Option<&str>) -> PlaylistEntry {
+            PlaylistEntry {
+                duration,
+                path: self.base().join(file),
+                title: title.map(|x| x.to_string()),
+            }
+        }
+    }
+
+    fn raw(location: &str, duration: Option<f64>, title: Option<&str>) -> super::RawEntry {
+        (location.to_string(), duration, title.map(|x| x.to_string()))
+    }
+
+    #[test]
+    fn parses_m3u() {
+        let content = "\
+            #EXTM3U\n\
+            #EXTINF:123 tvg-id=\"x\",Artist - Title\n\
+            a.mp3\n\
+            \n\
+            # comment\n\
+            #EXTINF:-1,\n\
+            http://example.com/stream\n\
+            sub/d.ogg\n";
+
+        assert_eq!(
+            parse_m3u(content),
+            vec![
+                raw("a.mp3", Some(123.0), Some("Artist - Title")),
+                raw("http://example.com/stream", None, None),
+                raw("sub/d.ogg", None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_pls() {
+        let content = "\
+            [playlist]\n\
+            Title2=Second\n\
+            File2=sub/d.ogg\n\
+            file1 = a.mp3\n\
+            Length1=61.5\n\
+            Length2=-1\n\
+            Title3=No file\n\
+            NumberOfEntries=3\n";
+
+        assert_eq!(
+            parse_pls(content),
+            vec![
+                raw("a.mp3", Some(61.5), None),
+                raw("sub/d.ogg", None, Some("Second")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_xspf() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <playlist version="1" xmlns="http://xspf.org/ns/0/">
+              <trackList>
+                <track>
+                  <location>b%20c.mp3</location>
+                  <title> Title </title>
+                  <duration>2500</duration>
+                </track>
+                <track><title>No location</title></track>
+                <track><location>sub/d.ogg</location><title></title></track>
+              </trackList>
+            </playlist>"#;
+
+        assert_eq!(
+            parse_xspf(content).unwrap(),
+            vec![
+                raw("b%20c.mp3", Some(2.5), Some("Title")),
+                raw("sub/d.ogg", None, None),
+            ]
+        );
+        assert!(matches!(parse_xspf("<playlist>"), Err(Error::Xml(_))));
+    }
+
+    #[test]
+    fn resolves_relative_paths() {
+        let fixture = Fixture::new();
+        let file_url = Url::from_file_path(fixture.base().join("a.mp3")).unwrap();
+        let content = format!(
+            "\u{feff}#EXTINF:10,A\nsub/d.ogg\nb c.mp3\nb%20c.mp3\n{file_url}\nmissing.mp3\n\
+             http://example.com/stream\n"
+        );
+
+        assert_eq!(
+            fixture.load("list.m3u8", &content),
+            Playlist {
+                entries: vec![
+                    fixture.entry("sub/d.ogg", Some(10.0), Some("A")),
+                    fixture.entry("b c.mp3", None, None),
+                    fixture.entry("b c.mp3", None, None),
+                    fixture.entry("a.mp3", None, None),
+                ],
+                missing: vec![
+                    "missing.mp3".to_string(),
+                    "http://example.com/stream".to_string()
+                ],
+            }
+        );
+
+        // Playlists in a subdirectory resolve against their own directory
+        assert_eq!(
+            fixture.load("sub/list.pls", "File1=d.ogg\nFile2=../a.mp3\n"),
+            Playlist {
+                entries: vec![
+                    fixture.entry("sub/d.ogg", None, None),
+                    fixture.entry("sub/../a.mp3", None, None),
+                ],
+                missing: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_format() {
+        let fixture = Fixture::new();
+        let path = fixture.base().join("list.txt");
+        write(&path, "a.mp3").unwrap();
+
+        assert!(matches!(
+            Playlist::load(&path),
+            Err(Error::UnsupportedPlaylist(x)) if x == path
+        ));
+    }
+
+    #[test]
+    fn fills_track_metadata() {
+        let fixture = Fixture::new();
+        let playlist = fixture.load(
+            "list.m3u",
+            "#EXTINF:42,Artist - Title\na.mp3\n#EXTINF:-1,\nsub/d.ogg\n",
+        );
+        let entries: PlaylistEntries = playlist
+            .entries
+            .into_iter()
+            .map(|x| (x.path.clone(), x))
+            .collect();
+        let load = |path: PathBuf| TrackMetadata::load(&path, entries.get(&path));
+
+        // The files are empty, so only the playlist provides metadata
+        let metadata = load(fixture.base().join("a.mp3"));
+        assert_eq!(metadata.title.as_deref(), Some("Artist - Title"));
+        assert_eq!(metadata.duration, Some(42.0));
+        assert_eq!(metadata.file_name, "a.mp3");
+
+        let metadata = load(fixture.base().join("sub").join("d.ogg"));
+        assert_eq!(metadata.title, None);
+        assert_eq!(metadata.duration, None);
+    }
+}
@@ -1,7 +1,12 @@
-use std::{collections::HashMap, ffi::c_void, fs::create_dir_all, path::PathBuf, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError},
+    time::Duration,
+};
 
-use bass_sys::{BASS_SYNC_END, BASS_SYNC_ONETIME, BASS_SYNC_SLIDE, DWORD, HSYNC, SYNCPROC};
-use log::{error, LevelFilter};
+use log::{error, warn, LevelFilter};
 use log4rs::{
     append::file::FileAppender,
     config::{Appender, Root},
@@ -11,26 +16,103 @@ use rand::{
     random,
     seq::{IteratorRandom, SliceRandom},
 };
-use tokio::sync::RwLock;
+use tokio::{
+    sync::{Mutex, RwLock},
+    time::{sleep_until, Instant},
+};
 
 use crate::{
-    audio_player::{BassStream, Player},
-    constants::{APP_PATH, CONFIG_PATH, LIST_EXTENSION},
-    data_path,
-    func::{lines_from_file, load_sound_lists},
-    Config, Result,
+    attract::Attract,
+    audio_player::{AudioBackend, Channel, LoopSection, NullBackend, Player, TrackMetadata},
+    background::{next_background, winner_background},
+    constants::{CONFIG_PATH, DATA_PATH},
+    device_monitor::DeviceMonitor,
+    event::{Event, Events, NowPlaying},
+    func::{list_items, load_sound_lists, sound_files_from_dir},
+    image_cache::prune_image_cache,
+    list_info::ListInfo,
+    playlist::{PlaylistEntries, PlaylistEntry},
+    rotation::Rotation,
+    sequencer::{
+        fade_out_music, play_first_playable, play_stop_sound, Cue, PickSound, QueuedStopSound,
+        Sequencer, StopSound,
+    },
+    track_info::TrackInfo,
+    Config, Error, MotionMode, MusicTransition, Result,
 };
 
 const LOG_FILE_NAME: &str = "randomizer.log";
+const LOUDNESS_FILE_NAME: &str = "loudness.toml";
+/// Time between wheel updates
+const STEP: Duration = Duration::from_millis(100);
+/// Tick playback rate increase per entry passed in one step
+const TICK_PITCH_PER_SPEED: f64 = 0.1;
+const TICK_PITCH_MAX: f64 = 2.0;
+/// Shortest spin that can be timed to a music cue, in seconds
+const CUE_MIN_TIME: f64 = 1.0;
+/// Music left after a random start position up to the loop end, in seconds
+const RANDOM_START_MARGIN: f64 = 30.0;
+/// Number of frequency bands in the music spectrum
+const SPECTRUM_BANDS: usize = 16;
+/// Lowest music playback rate, zero rate means the original one for BASS
+const MUSIC_SPEED_LOWEST: f64 = 0.05;
+#[cfg(not(feature = "rodio"))]
 const PLUGINS_DIR: &str = "plugins";
 
+/// Metadata of sounds by category and list name, common sounds are under the empty list name
+pub type SoundLibrary = BTreeMap<String, BTreeMap<String, Vec<TrackMetadata>>>;
+
+/// Roll music with its cue points and credits
+struct Music {
+    info: TrackInfo,
+    metadata: TrackMetadata,
+    path: PathBuf,
+}
+
+impl Music {
+    /// Reads the tags once for both cue points and credits
+    fn load(path: PathBuf, playlist_entry: Option<&PlaylistEntry>) -> Self {
+        let file = lofty::read_from_path(&path)
+            .map_err(|e| error!("Failed to read tags of {}: {:#?}", path.display(), e))
+            .ok();
+        Self {
+            info: TrackInfo::with_tags(&path, file.as_ref()),
+            metadata: TrackMetadata::with_tags(&path, file.as_ref(), playlist_entry),
+            path,
+        }
+    }
+}
+
+impl AsRef<Path> for Music {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
 pub struct Randomizer {
+    attract: Attract,
+    /// Reason why audio failed to initialize, the randomizer stays silent until it's fixed
+    audio_error: Option<String>,
     config: Config,
-    current_list: RwLock<Option<String>>,
-    player: RwLock<Player>,
+    /// Directory with lists, sounds and backgrounds
+    data_path: PathBuf,
+    device_monitor: DeviceMonitor,
+    events: Events,
+    /// File loudness measurements are saved to, `None` keeps them in memory
+    loudness_cache: Option<PathBuf>,
+    /// Loudness of the sounds has been measured for the current player
+    loudness_scanned: bool,
+    player: Arc<RwLock<Player>>,
+    /// Titles and durations from the playlists of the sounds, shared with the sequencer
+    playlist_entries: Arc<PlaylistEntries>,
     roll_sounds: HashMap<String, Vec<PathBuf>>,
+    rolling: Mutex<()>,
+    /// Locked from audio callbacks too, so it's a blocking mutex. Shared with the attract mode
+    rotation: Arc<std::sync::Mutex<Rotation>>,
+    sequencer: Sequencer,
     stop_roll: RwLock<bool>,
     stop_sounds: HashMap<String, Vec<PathBuf>>,
+    tick_sounds: HashMap<String, Vec<PathBuf>>,
 }
 
 impl Randomizer {
@@ -40,93 +122,411 @@ impl Randomizer {
         init_log()?;
 
         let config = Config::load();
-        let mut plugins_dir = APP_PATH.clone();
-        plugins_dir.push(PLUGINS_DIR);
-        Player::init(plugins_dir, config.audio_device().as_str(), config.volume())?;
+        let (backend, audio_error) = init_backend(&config);
+
+        let mut result = Self::with_backend(config, backend, DATA_PATH.as_path());
+        result.audio_error = audio_error;
+        *result
+            .rotation
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Rotation::load();
+        result.loudness_cache = Some(CONFIG_PATH.join(LOUDNESS_FILE_NAME));
+        result.scan_loudness();
+        prune_image_cache(DATA_PATH.as_path());
+
+        Ok(result)
+    }
 
-        Ok(Self {
+    /// Creates randomizer that plays audio through `backend`, unlike `new` it does not initialize
+    /// logging, does not read config from disk and does not measure loudness until config is set.
+    /// Lists, sounds and backgrounds are read from `data_path`
+    pub fn with_backend(config: Config, backend: Box<dyn AudioBackend>, data_path: &Path) -> Self {
+        let config = config.normalize();
+        let player = Arc::new(RwLock::new(create_player(backend, &config)));
+        let events = Events::new();
+        let rotation = Arc::new(std::sync::Mutex::new(Rotation::default()));
+        let mut playlist_entries = PlaylistEntries::new();
+        let roll_sounds = load_sound_lists(data_path, "roll", &mut playlist_entries);
+        let stop_sounds = load_sound_lists(data_path, "stop", &mut playlist_entries);
+        let tick_sounds = load_sound_lists(data_path, "tick", &mut playlist_entries);
+        let playlist_entries = Arc::new(playlist_entries);
+        let sequencer = Sequencer::spawn(&player, events.clone(), playlist_entries.clone());
+        let attract = Attract::spawn(
+            &player,
+            events.clone(),
+            rotation.clone(),
+            data_path,
+            &config,
+        );
+        let device_monitor = DeviceMonitor::spawn(&player, events.clone(), config.audio_device());
+
+        Self {
+            attract,
+            audio_error: None,
             config,
-            current_list: RwLock::new(None),
-            player: RwLock::new(Player::new()),
-            roll_sounds: load_sound_lists("roll"),
+            data_path: data_path.to_path_buf(),
+            device_monitor,
+            events,
+            loudness_cache: None,
+            loudness_scanned: false,
+            player,
+            playlist_entries,
+            roll_sounds,
+            rolling: Mutex::new(()),
+            rotation,
+            sequencer,
             stop_roll: RwLock::new(false),
-            stop_sounds: load_sound_lists("stop"),
-        })
+            stop_sounds,
+            tick_sounds,
+        }
+    }
+
+    pub fn audio_devices(&self) -> Result<Vec<(String, String)>> {
+        if self.audio_error.is_some() {
+            // Device list may be unavailable too, settings still have to open
+            return Ok(default_devices().unwrap_or_else(|e| {
+                error!("{:#?}", e);
+                vec![]
+            }));
+        }
+
+        self.player.blocking_read().list_devices()
+    }
+
+    /// Returns reason why audio is unavailable, `None` when audio works
+    pub fn audio_error(&self) -> Option<&str> {
+        self.audio_error.as_deref()
     }
 
     pub fn config(&self) -> &Config {
         &self.config
     }
 
-    pub async fn roll(&self, list_name: &str, names_callback: impl Fn(Vec<String>)) -> Result<()> {
-        {
-            let mut w = self.current_list.write().await;
-            *w = Some(list_name.to_string());
+    pub fn events(&self) -> &Events {
+        &self.events
+    }
 
-            let mut w = self.stop_roll.write().await;
-            *w = false;
+    /// Reads metadata of all sounds, e.g. for music credits
+    pub fn sound_library(&self) -> SoundLibrary {
+        [
+            ("roll", &self.roll_sounds),
+            ("stop", &self.stop_sounds),
+            ("tick", &self.tick_sounds),
+        ]
+        .into_iter()
+        .map(|(category, sounds)| {
+            let lists = sounds
+                .iter()
+                .map(|(list_name, paths)| {
+                    let mut paths = paths.clone();
+                    paths.sort();
+                    let tracks = paths
+                        .iter()
+                        .map(|x| TrackMetadata::load(x, self.playlist_entries.get(x)))
+                        .collect();
+                    (list_name.clone(), tracks)
+                })
+                .collect();
+            (category.to_string(), lists)
+        })
+        .collect()
+    }
+
+    /// Picks the next background of the list, relative to the data directory
+    pub fn random_bg(&self, list_name: &str) -> Result<Option<String>> {
+        next_background(&self.data_path, &self.rotation, list_name)
+    }
+
+    pub async fn roll(&self, list_name: &str) -> Result<()> {
+        let _rolling = self.rolling.try_lock().map_err(|_| Error::AlreadyRolling)?;
+
+        self.attract.set_rolling(list_name, true);
+        self.events.emit(Event::Roll(list_name.to_string()));
+        let result = self.spin(list_name).await;
+        self.events.emit(Event::Stop);
+        self.attract.set_rolling(list_name, false);
+        self.save_rotation();
+
+        result
+    }
+
+    /// Saves the sound and background rotation, it's saved after every roll and should be
+    /// saved on exit for the picks in between
+    pub fn save_rotation(&self) {
+        self.rotation
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .save();
+    }
+
+    pub fn set_config(&mut self, config: Config) -> Result<()> {
+        self.config = config.normalize();
+        self.device_monitor.set_driver(self.config.audio_device());
+        self.attract.set_config(&self.config);
+        if self.audio_error.is_some() {
+            self.retry_audio();
+        } else {
+            let mut player = self.player.blocking_write();
+            player.set_volume(self.config.volume())?;
+            player.set_device(self.config.audio_device())?;
+            player.set_cache_size(self.config.audio_cache_size());
+            player.set_normalize(self.config.normalize_loudness());
+            set_channel_volumes(&mut player, &self.config)?;
         }
+        self.scan_loudness();
+        self.config.save()?;
+        self.events
+            .emit(Event::Config(Box::new(self.config.clone())));
 
-        if self.config.music() {
-            if let Some(sound_path) = random_sound(&self.roll_sounds, Some(list_name)) {
-                let stream = BassStream::from_file(&sound_path.to_string_lossy(), true)?;
-                stream.set_sync(
-                    BASS_SYNC_SLIDE | BASS_SYNC_ONETIME,
-                    0,
-                    callback_music as *mut SYNCPROC,
-                    self as *const Randomizer as *mut c_void,
-                )?;
-                {
-                    let mut player = self.player.write().await;
-                    player.set_stream(Some(stream));
-                    player.play(false)?;
-                }
-            }
+        Ok(())
+    }
+
+    /// Registers user activity, it stops the attract mode and restarts the idle timer.
+    /// `list_name` is the selected list the attract mode shows
+    pub fn wake(&self, list_name: &str) {
+        self.attract.wake(list_name);
+    }
+
+    /// Changes playback volume without saving it to the config
+    pub fn set_volume(&self, volume: u32) -> Result<()> {
+        self.player.blocking_read().set_volume(volume)
+    }
+
+    /// Tries to initialize audio again with the current config, does nothing if audio works
+    pub fn retry_audio(&mut self) {
+        if self.audio_error.is_none() {
+            return;
+        }
+
+        let (backend, audio_error) = init_backend(&self.config);
+        if audio_error.is_none() {
+            *self.player.blocking_write() = create_player(backend, &self.config);
+            self.loudness_scanned = false;
+        }
+        self.audio_error = audio_error;
+        self.events
+            .emit(Event::AudioError(self.audio_error.clone()));
+    }
+
+    /// Starts measuring loudness of all sounds if normalization is on and they aren't measured
+    fn scan_loudness(&mut self) {
+        if self.loudness_scanned || !self.config.normalize_loudness() || self.audio_error.is_some()
+        {
+            return;
         }
 
-        let mut path = data_path();
-        path.push("lists");
-        path.push(format!("{list_name}.{LIST_EXTENSION}"));
+        let paths: HashSet<PathBuf> = [&self.roll_sounds, &self.stop_sounds, &self.tick_sounds]
+            .into_iter()
+            .flat_map(|x| x.values().flatten().cloned())
+            .collect();
+        self.player
+            .blocking_read()
+            .scan_loudness(paths.into_iter().collect(), self.loudness_cache.clone());
+        self.loudness_scanned = true;
+    }
+
+    pub fn stop_roll(&self) {
+        let mut w = self.stop_roll.blocking_write();
+        *w = true;
+        self.events.emit(Event::Stopping);
+    }
 
+    async fn spin(&self, list_name: &str) -> Result<()> {
         let items = {
             let mut rng = rand::thread_rng();
-            let mut lines = lines_from_file(path);
+            let mut lines = list_items(&self.data_path, list_name)?;
             lines.shuffle(&mut rng);
             lines
         };
+        if items.is_empty() {
+            return Err(Error::EmptyList(list_name.to_string()));
+        }
+
+        {
+            let mut w = self.stop_roll.write().await;
+            *w = false;
+        }
+
+        // Tags of the music are read before the player is locked, only music picked in place of
+        // an unplayable one is read under the lock
+        let mut music = if self.config.music() {
+            self.next_music(list_name, &HashSet::new())
+        } else {
+            None
+        };
+
+        let mut cue_time = None;
+        let mut tick_sound = None;
+        {
+            let mut player = self.player.write().await;
+            player.stop_all();
+            self.sequencer.reset();
+            if self.config.music() {
+                let music = play_first_playable(
+                    |exclude| music.take().or_else(|| self.next_music(list_name, exclude)),
+                    |music| {
+                        let loop_section = music.info.loop_section();
+                        let track = player.create_stream(&music.path, Some(loop_section))?;
+                        if self.config.music_random_start() {
+                            if let Some(position) =
+                                random_start(loop_section, music.metadata.duration)
+                            {
+                                track.seek(position)?;
+                            }
+                        }
+                        player.play(Channel::Music, track, 0)
+                    },
+                )?;
+                if let Some(Music { info, metadata, .. }) = music {
+                    self.events.emit(Event::MusicStarted);
+                    self.events.emit(NowPlaying::track_event("roll", metadata));
+
+                    if self.config.motion_mode() == MotionMode::MusicCue {
+                        cue_time = self.time_until_cue(&player, info.stop_at)?;
+                    }
+                }
+            }
+
+            if self.config.ticks() {
+                let result = play_first_playable(
+                    |exclude| {
+                        let (_, sounds) = sound_candidates(&self.tick_sounds, list_name, exclude)?;
+                        sounds
+                            .iter()
+                            .filter(|x| !exclude.contains(*x))
+                            .choose(&mut rand::thread_rng())
+                            .cloned()
+                    },
+                    |sound_path| player.preload(sound_path),
+                );
+                // Roll goes on without ticks if the sound can't be loaded
+                match result {
+                    Ok(sound_path) => tick_sound = sound_path,
+                    Err(e) => error!("{:#?}", e),
+                }
+            }
+        }
+
+        // Stop sounds are read while the wheel spins, so they start without a delay
+        let mut stop_sounds: Vec<PathBuf> = entry_stop_sounds(&self.data_path, list_name, &items)
+            .into_values()
+            .collect();
+        if let Some((_, sounds)) = sound_candidates(&self.stop_sounds, list_name, &HashSet::new()) {
+            stop_sounds.extend(sounds.iter().cloned());
+        }
+        self.player.read().await.cache_files(stop_sounds);
 
         let mut i: f64 = 0.0;
         let total = items.len() as isize;
 
         let mut speed = self.config.speed_start();
+        let start_speed = speed;
+        // Even reduction that makes the speed negative right after the step that ends at the cue
+        let cue_reduce = cue_time.map(|time| {
+            let steps = (time / STEP.as_secs_f64()).round().max(1.0);
+            speed / (steps - 0.5)
+        });
         let direction = if random::<f64>() < self.config.reverse_chance() {
             -1.0
         } else {
             1.0
         };
 
+        let slideshow = match ListInfo::load(&self.data_path, list_name)
+            .background
+            .slideshow
+        {
+            0 => None,
+            seconds => Some(Duration::from_secs(seconds.into())),
+        };
+
+        let mut last_pos = i.round() as isize;
+        // Steps are timed from the start, so the delays don't add up over a long spin
+        let mut deadline = Instant::now();
+        let mut next_slide = slideshow.map(|interval| deadline + interval);
         loop {
             let pos = (i.round() as isize).rem_euclid(total);
-            names_callback(
+            self.events.emit(Event::WheelList(
                 (pos - 2..pos + 3)
                     .map(|x| items[x.rem_euclid(total) as usize].to_string())
                     .collect::<Vec<String>>(),
-            );
+            ));
+            if self.config.spectrum() {
+                match self
+                    .player
+                    .read()
+                    .await
+                    .spectrum(Channel::Music, SPECTRUM_BANDS)
+                {
+                    Ok(Some(bands)) => self.events.emit(Event::Spectrum(bands)),
+                    Ok(None) => (),
+                    Err(e) => error!("{:#?}", e),
+                }
+            }
 
-            tokio::time::sleep(Duration::from_millis(100)).await;
+            if let (Some(slide), Some(interval)) = (next_slide, slideshow) {
+                if Instant::now() >= slide {
+                    next_slide = Some(slide + interval);
+                    match self.random_bg(list_name) {
+                        Ok(file_path) => self.events.emit(Event::Background(file_path)),
+                        Err(e) => error!("{:#?}", e),
+                    }
+                }
+            }
+
+            let passed = (i.round() as isize - last_pos).unsigned_abs();
+            last_pos = i.round() as isize;
+            deadline += STEP;
+            match &tick_sound {
+                Some(tick_sound) => {
+                    self.step_with_ticks(tick_sound, passed, speed, deadline)
+                        .await;
+                }
+                None => sleep_until(deadline).await,
+            }
             i += speed * direction;
-            speed -= if speed < self.config.speed_slow_limit() {
-                self.config.speed_slow_reduce()
-            } else {
-                self.config.speed_reduce()
+            speed -= match cue_reduce {
+                Some(cue_reduce) => cue_reduce,
+                None if speed < self.config.speed_slow_limit() => self.config.speed_slow_reduce(),
+                None => self.config.speed_reduce(),
             };
 
             if *self.stop_roll.read().await {
                 speed = speed.min(self.config.speed_stop());
             }
 
+            // Playback rate changes would move the cue
+            if self.config.music_follow_speed() && cue_reduce.is_none() {
+                // Music plays at the upper bound at the starting speed and at the lower one when
+                // the wheel stops
+                let min = self.config.music_speed_min();
+                let max = self.config.music_speed_max();
+                let rate = (min + (max - min) * (speed / start_speed).clamp(0.0, 1.0))
+                    .max(MUSIC_SPEED_LOWEST);
+                if let Err(e) = self
+                    .player
+                    .read()
+                    .await
+                    .set_speed(Channel::Music, rate as f32)
+                {
+                    error!("{:#?}", e);
+                }
+            }
+
             if speed < 0.0 {
-                self.player.write().await.fade_out()?;
+                let winner = items[pos as usize].to_string();
+                match winner_background(&self.data_path, list_name, &winner) {
+                    Ok(Some(file_path)) => self.events.emit(Event::Background(Some(file_path))),
+                    Ok(None) => (),
+                    Err(e) => error!("{:#?}", e),
+                }
+                self.events.emit(Event::Winner(winner.clone()));
+                // The winner stands even if the stop sound can't be played
+                if let Err(e) =
+                    self.play_outro(&mut *self.player.write().await, list_name, &items, &winner)
+                {
+                    error!("{:#?}", e);
+                }
                 break;
             }
         }
@@ -134,45 +534,253 @@ impl Randomizer {
         Ok(())
     }
 
-    pub fn set_config(&mut self, config: Config) -> Result<()> {
-        self.config = config;
-        Player::set_volume(self.config.volume())?;
-        Player::set_device(Player::find_device_index(self.config.audio_device())?)?;
-        self.config.save()
+    /// Returns time in seconds from now to the cue point of the playing music, `None` if the
+    /// track has no cue ahead of its position
+    fn time_until_cue(&self, player: &Player, stop_at: Option<f64>) -> Result<Option<f64>> {
+        let stop_at = match stop_at {
+            Some(stop_at) => stop_at,
+            None => return Ok(None),
+        };
+        let position = player.position(Channel::Music)?.unwrap_or_default();
+
+        Ok(Some(stop_at - position).filter(|time| *time >= CUE_MIN_TIME))
     }
 
-    pub fn stop_roll(&self) {
-        let mut w = self.stop_roll.blocking_write();
-        *w = true;
+    /// Waits until `deadline` that ends the wheel step, playing a tick for every passed entry.
+    /// Ticks are spread over the step and merged when they would be closer than the minimum
+    /// interval
+    async fn step_with_ticks(
+        &self,
+        tick_sound: &Path,
+        passed: usize,
+        speed: f64,
+        deadline: Instant,
+    ) {
+        let max_ticks = (STEP.as_millis() as u32 / self.config.tick_min_interval().max(1)).max(1);
+        let ticks = (passed as u32).min(max_ticks);
+        if ticks == 0 {
+            sleep_until(deadline).await;
+            return;
+        }
+
+        let rate = if self.config.tick_pitch() {
+            (1.0 + speed.max(0.0) * TICK_PITCH_PER_SPEED).min(TICK_PITCH_MAX) as f32
+        } else {
+            1.0
+        };
+
+        let interval = STEP / ticks;
+        let start = deadline - STEP;
+        for tick in 0..ticks {
+            sleep_until(start + interval * tick).await;
+            if let Err(e) = self
+                .player
+                .read()
+                .await
+                .play_sample(Channel::Tick, tick_sound, rate)
+            {
+                error!("{:#?}", e);
+            }
+        }
+        sleep_until(deadline).await;
     }
 
-    pub(crate) fn play_stop_sound(&self) -> Result<()> {
-        if let Some(sound_path) = random_sound(
-            &self.stop_sounds,
-            self.current_list.blocking_read().as_deref(),
-        ) {
-            let stream = BassStream::from_file(&sound_path.to_string_lossy(), false)?;
-            stream.set_sync(
-                BASS_SYNC_END | BASS_SYNC_ONETIME,
-                0,
-                callback_sound as *mut SYNCPROC,
-                self as *const Randomizer as *mut c_void,
-            )?;
+    /// Moves from the roll music to the stop sound of the winner according to the music
+    /// transition, the sequencer takes over from there
+    fn play_outro(
+        &self,
+        player: &mut Player,
+        list_name: &str,
+        items: &[String],
+        winner: &str,
+    ) -> Result<()> {
+        let fade_out = self.config.music_fade_out();
+        let transition = self.config.music_transition();
+        let sender = self.sequencer.sender();
+        let generation = self.sequencer.generation();
+        let pick = self.stop_sound_picker(list_name, items, winner);
+        let stop_sound = |path: &Path| StopSound {
+            fade_in: self.config.stop_sound_fade_in(),
+            // Ducked music is still playing and fades out after the stop sound
+            music_fade_out: (transition == MusicTransition::Duck).then_some(fade_out),
+            path: path.to_path_buf(),
+        };
 
-            let mut player = self.player.blocking_write();
-            player.set_stream(Some(stream));
-            player.play(false)?;
+        match transition {
+            MusicTransition::Sequential if player.is_active(Channel::Music) => {
+                // Queued before the fade starts, so the fade can't end first
+                self.sequencer.send(Cue::QueueStopSound(QueuedStopSound {
+                    fade_in: self.config.stop_sound_fade_in(),
+                    music_fade_out: None,
+                    pick,
+                }));
+                fade_out_music(player, fade_out, sender, generation)?;
+            }
+            MusicTransition::Duck => {
+                let played = play_first_playable(pick, |path| {
+                    let stop_sound = stop_sound(path);
+                    player.duck(
+                        Channel::Music,
+                        self.config.music_duck_volume() as f32 / 100.0,
+                        stop_sound.fade_in,
+                    )?;
+                    play_stop_sound(player, &stop_sound, sender, generation)
+                })?;
+                match played {
+                    Some(path) => self.emit_stop_sound(&path),
+                    None => {
+                        fade_out_music(player, fade_out, sender, generation)?;
+                    }
+                }
+            }
+            _ => {
+                fade_out_music(player, fade_out, sender, generation)?;
+                let played = play_first_playable(pick, |path| {
+                    play_stop_sound(player, &stop_sound(path), sender, generation)
+                })?;
+                if let Some(path) = played {
+                    self.emit_stop_sound(&path);
+                }
+            }
         }
 
         Ok(())
     }
 
-    pub(crate) fn stop_audio(&self) {
-        let mut player = self.player.blocking_write();
-        player.stop();
+    fn emit_stop_sound(&self, path: &Path) {
+        self.events
+            .emit(NowPlaying::event("stop", path, &self.playlist_entries));
+    }
+
+    /// Picks next roll music for the list and reads its tags, `exclude` music is never picked
+    fn next_music(&self, list_name: &str, exclude: &HashSet<PathBuf>) -> Option<Music> {
+        let path = self.next_sound("roll", &self.roll_sounds, list_name, exclude)?;
+        let playlist_entry = self.playlist_entries.get(&path);
+        Some(Music::load(path, playlist_entry))
+    }
+
+    /// Picks next sound of the category for the list according to the rotation mode, `exclude`
+    /// sounds are never picked
+    fn next_sound(
+        &self,
+        category: &str,
+        sounds: &HashMap<String, Vec<PathBuf>>,
+        list_name: &str,
+        exclude: &HashSet<PathBuf>,
+    ) -> Option<PathBuf> {
+        let (sounds_list, sounds) = sound_candidates(sounds, list_name, exclude)?;
+        self.rotation
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pick(
+                self.config.sound_rotation(),
+                category,
+                sounds_list,
+                sounds,
+                exclude,
+            )
+    }
+
+    /// Returns picker of the stop sound for the winner: their own sound, then next sound of the
+    /// list, then a common one. Own sounds of other entries are left out of the rotation. The
+    /// picker owns what it needs, so the sequencer can pick after the music fades out
+    fn stop_sound_picker(&self, list_name: &str, items: &[String], winner: &str) -> PickSound {
+        let mut entry_sounds = entry_stop_sounds(&self.data_path, list_name, items);
+        let own_sound = entry_sounds.remove(&winner.to_lowercase());
+        let other_sounds: HashSet<PathBuf> = entry_sounds.into_values().collect();
+        let sounds: HashMap<String, Vec<PathBuf>> = [list_name, ""]
+            .into_iter()
+            .filter_map(|name| Some((name.to_string(), self.stop_sounds.get(name)?.clone())))
+            .collect();
+        let list_name = list_name.to_string();
+        let mode = self.config.sound_rotation();
+        let rotation = self.rotation.clone();
+
+        Box::new(move |exclude| {
+            if let Some(sound_path) = own_sound.as_ref().filter(|x| !exclude.contains(*x)) {
+                return Some(sound_path.clone());
+            }
+
+            let mut exclude = exclude.clone();
+            exclude.extend(other_sounds.iter().cloned());
+            let (sounds_list, sounds) = sound_candidates(&sounds, &list_name, &exclude)?;
+            rotation
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pick(mode, "stop", sounds_list, sounds, &exclude)
+        })
+    }
+}
+
+/// Returns random start position of the music in seconds, so at least `RANDOM_START_MARGIN`
+/// plays before it loops. `None` if the music is too short
+fn random_start(loop_section: LoopSection, duration: Option<f64>) -> Option<f64> {
+    let end = loop_section.end.or(duration)?;
+    let limit = end - RANDOM_START_MARGIN;
+
+    (limit > 0.0).then(|| random::<f64>() * limit)
+}
+
+fn create_player(backend: Box<dyn AudioBackend>, config: &Config) -> Player {
+    let mut result = Player::new(backend);
+    result.set_cache_size(config.audio_cache_size());
+    result.set_normalize(config.normalize_loudness());
+    if let Err(e) = set_channel_volumes(&mut result, config) {
+        error!("{:#?}", e);
+    }
+
+    result
+}
+
+fn set_channel_volumes(player: &mut Player, config: &Config) -> Result<()> {
+    player.set_channel_volume(Channel::Music, config.music_volume())?;
+    player.set_channel_volume(Channel::Stinger, config.stinger_volume())?;
+    player.set_channel_volume(Channel::Tick, config.tick_volume())?;
+
+    Ok(())
+}
+
+/// Creates the default backend, falls back to a silent one with the error message on failure
+fn init_backend(config: &Config) -> (Box<dyn AudioBackend>, Option<String>) {
+    match default_backend(config) {
+        Ok(backend) => (backend, None),
+        Err(e) => {
+            error!("Audio is unavailable: {:#?}", e);
+            (Box::new(NullBackend::new()), Some(e.to_string()))
+        }
     }
 }
 
+#[cfg(not(feature = "rodio"))]
+fn default_backend(config: &Config) -> Result<Box<dyn AudioBackend>> {
+    use crate::{audio_player::BassBackend, constants::APP_PATH};
+
+    let mut plugins_dir = APP_PATH.clone();
+    plugins_dir.push(PLUGINS_DIR);
+    let backend = BassBackend::init(plugins_dir, config.audio_device().as_str(), config.volume())?;
+
+    Ok(Box::new(backend))
+}
+
+#[cfg(feature = "rodio")]
+fn default_backend(config: &Config) -> Result<Box<dyn AudioBackend>> {
+    use crate::audio_player::RodioBackend;
+
+    let backend = RodioBackend::init(config.audio_device().as_str(), config.volume())?;
+
+    Ok(Box::new(backend))
+}
+
+#[cfg(not(feature = "rodio"))]
+fn default_devices() -> Result<Vec<(String, String)>> {
+    crate::audio_player::BassBackend::devices()
+}
+
+#[cfg(feature = "rodio")]
+fn default_devices() -> Result<Vec<(String, String)>> {
+    crate::audio_player::RodioBackend::devices()
+}
+
 fn init_log() -> Result<()> {
     log_panics::init();
 
@@ -199,47 +807,192 @@ fn init_log() -> Result<()> {
     Ok(())
 }
 
-fn callback_music(_handle: HSYNC, _channel: DWORD, _data: DWORD, user: *mut c_void) {
-    if user.is_null() {
-        return;
-    }
+/// Returns own stop sounds of the list entries by lowercase entry text. Entry metadata takes
+/// precedence over files named after the entry in `data/sounds/stop/<list>`
+fn entry_stop_sounds(
+    data_path: &Path,
+    list_name: &str,
+    items: &[String],
+) -> HashMap<String, PathBuf> {
+    let mut stop_dir = data_path.to_path_buf();
+    stop_dir.push("sounds");
+    stop_dir.push("stop");
+
+    let entries: HashSet<String> = items.iter().map(|x| x.to_lowercase()).collect();
+    let mut result: HashMap<String, PathBuf> = sound_files_from_dir(&stop_dir.join(list_name))
+        .filter_map(|path| {
+            let entry = path.file_stem()?.to_string_lossy().to_lowercase();
+            entries.contains(&entry).then_some((entry, path))
+        })
+        .collect();
 
-    let randomizer: &Randomizer = unsafe { &*(user as *const Randomizer) };
-    if let Err(e) = randomizer.play_stop_sound() {
-        error!("{:#?}", e);
+    for (entry, info) in ListInfo::load(data_path, list_name).entries {
+        if let Some(stop_sound) = info.stop_sound {
+            let path = stop_dir.join(stop_sound);
+            if path.is_file() {
+                result.insert(entry.to_lowercase(), path);
+            } else {
+                warn!("Stop sound {} of {} is missing", path.display(), entry);
+            }
+        }
     }
+
+    result
+}
+
+/// Returns sounds of the list or common sounds if the list has none, along with the list name
+/// they belong to, empty for common sounds. A list whose sounds are all in `exclude` counts as
+/// having none, the returned sounds still include the excluded ones
+pub(crate) fn sound_candidates<'a>(
+    sounds: &'a HashMap<String, Vec<PathBuf>>,
+    list_name: &'a str,
+    exclude: &HashSet<PathBuf>,
+) -> Option<(&'a str, &'a [PathBuf])> {
+    [list_name, ""].into_iter().find_map(|name| {
+        let result = sounds.get(name)?;
+        result
+            .iter()
+            .any(|x| !exclude.contains(x))
+            .then_some((name, result.as_slice()))
+    })
 }
 
-fn callback_sound(_handle: HSYNC, _channel: DWORD, _data: DWORD, user: *mut c_void) {
-    if user.is_null() {
-        return;
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, write},
+        path::{Path, PathBuf},
+        thread,
+        time::Duration,
+    };
+
+    use tempfile::TempDir;
+    use tokio::runtime::Builder;
+
+    use super::Randomizer;
+    use crate::{AudioCall, Config, Error, Event, LoopSection, NullBackend};
+
+    /// Music fade-out time of the test config, ms
+    const FADE_OUT: u32 = 50;
+
+    /// Temporary data directory with a list and a sound of every category
+    struct DataDir {
+        dir: TempDir,
     }
 
-    let randomizer: &Randomizer = unsafe { &*(user as *const Randomizer) };
-    randomizer.stop_audio();
-}
+    impl DataDir {
+        fn new() -> Self {
+            let dir = TempDir::new().unwrap();
+            let path = dir.path();
+            for dir in ["lists", "sounds/roll", "sounds/stop", "sounds/tick"] {
+                create_dir_all(path.join(dir)).unwrap();
+            }
+            write(
+                path.join("lists/games.txt"),
+                "Doom\n# comment\nQuake\nTetris\n",
+            )
+            .unwrap();
+            write(path.join("lists/empty.txt"), "").unwrap();
+            for file in [
+                "sounds/roll/music.mp3",
+                "sounds/stop/stop.wav",
+                "sounds/tick/tick.wav",
+            ] {
+                write(path.join(file), "").unwrap();
+            }
 
-fn random_sound(
-    sounds: &HashMap<String, Vec<PathBuf>>,
-    list_name: Option<&str>,
-) -> Option<PathBuf> {
-    let mut rng = rand::thread_rng();
+            Self { dir }
+        }
 
-    let mut playlists = Vec::with_capacity(2);
-    if let Some(list_name) = list_name {
-        if let Some(playlist) = sounds.get(list_name).map(|x| x.iter()) {
-            playlists.push(playlist);
+        fn path(&self) -> &Path {
+            self.dir.path()
+        }
+
+        fn sound(&self, file: &str) -> PathBuf {
+            self.path().join("sounds").join(file)
         }
     }
-    if let Some(playlist) = sounds.get("").map(|x| x.iter()) {
-        playlists.push(playlist);
+
+    /// Config of a spin that stops after a few steps
+    fn short_spin() -> Config {
+        toml::from_str(
+            "speedStartMin = 1.0\n\
+             speedStartMax = 1.1\n\
+             speedReduceMin = 0.4\n\
+             speedReduceMax = 0.5\n\
+             speedSlowLimit = 0.0\n\
+             reverseChance = 0.0\n\
+             musicFadeOut = 50",
+        )
+        .unwrap()
     }
 
-    for playlist in playlists {
-        if let Some(entry) = playlist.choose(&mut rng) {
-            return Some(entry.clone());
+    fn roll(data_path: &Path, list_name: &str) -> (NullBackend, Vec<Event>, Result<(), Error>) {
+        let backend = NullBackend::new();
+        let randomizer =
+            Randomizer::with_backend(short_spin(), Box::new(backend.clone()), data_path);
+        let mut receiver = randomizer.events().subscribe();
+
+        let runtime = Builder::new_current_thread().enable_time().build().unwrap();
+        let result = runtime.block_on(randomizer.roll(list_name));
+        // The sequencer plays the stop sound on its own thread after the fade
+        thread::sleep(Duration::from_millis(u64::from(FADE_OUT) + 200));
+
+        let mut events = vec![];
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event);
         }
+
+        (backend, events, result)
     }
 
-    None
+    #[test]
+    fn rolls_list_with_music_ticks_and_stop_sound() {
+        let data = DataDir::new();
+        let (backend, events, result) = roll(data.path(), "games");
+        result.unwrap();
+
+        let winner = events.iter().find_map(|event| match event {
+            Event::Winner(winner) => Some(winner.clone()),
+            _ => None,
+        });
+        assert!(
+            matches!(winner.as_deref(), Some("Doom" | "Quake" | "Tetris")),
+            "{winner:?}"
+        );
+        assert!(matches!(events.first(), Some(Event::Roll(x)) if x == "games"));
+        assert!(events.iter().any(|x| matches!(x, Event::Stop)));
+
+        let music = data.sound("roll/music.mp3");
+        let stop = data.sound("stop/stop.wav");
+        let tick = data.sound("tick/tick.wav");
+        let calls = backend.calls();
+        for call in [
+            AudioCall::CreateStream(music.clone(), Some(LoopSection::default())),
+            AudioCall::Play(music.clone()),
+            AudioCall::LoadSample(tick.clone()),
+            AudioCall::SlideVolume(music, -1.0, FADE_OUT),
+            // Stop sounds are cached during the spin, so the stream is created from memory
+            AudioCall::CreateStreamFromMemory(stop.clone(), None),
+            AudioCall::Play(stop),
+        ] {
+            assert!(calls.contains(&call), "{call:?} in {calls:#?}");
+        }
+        assert!(calls
+            .iter()
+            .any(|x| matches!(x, AudioCall::PlaySample(path, _, _) if *path == tick)));
+    }
+
+    #[test]
+    fn fails_on_missing_and_empty_lists() {
+        let data = DataDir::new();
+
+        let (backend, events, result) = roll(data.path(), "missing");
+        assert!(matches!(result, Err(Error::Io(_))), "{result:?}");
+        assert!(backend.calls().is_empty());
+        assert!(!events.iter().any(|x| matches!(x, Event::Winner(_))));
+
+        let (_, _, result) = roll(data.path(), "empty");
+        assert!(matches!(result, Err(Error::EmptyList(x)) if x == "empty"));
+    }
 }
@@ -0,0 +1,234 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+use log::error;
+use rand::seq::{IteratorRandom, SliceRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::SoundRotation, constants::CONFIG_PATH, Result};
+
+const ROTATION_FILE_NAME: &str = "rotation.toml";
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+struct History {
+    /// Tracks left in the shuffle bag, the next one is at the end
+    bag: Vec<PathBuf>,
+    /// Play number of every played track
+    last_played: HashMap<PathBuf, u64>,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+struct RotationState {
+    /// Play histories by "<category>/<list>" key, common sounds have empty list name
+    histories: HashMap<String, History>,
+    plays: u64,
+}
+
+/// Picks sounds and backgrounds so they don't repeat too often, history is kept per list and
+/// category
+#[derive(Default)]
+pub(crate) struct Rotation {
+    /// File the history is saved to, `None` keeps it in memory
+    path: Option<PathBuf>,
+    state: RotationState,
+    /// Whether there are picks that aren't saved yet
+    changed: bool,
+}
+
+impl Rotation {
+    /// Loads history saved in the config directory
+    pub fn load() -> Self {
+        let mut path = CONFIG_PATH.clone();
+        path.push(ROTATION_FILE_NAME);
+
+        let state = if path.is_file() {
+            read_state(&path).unwrap_or_else(|e| {
+                error!(
+                    "Failed to load sound rotation, starting over. Error: {:#?}",
+                    e
+                );
+                RotationState::default()
+            })
+        } else {
+            RotationState::default()
+        };
+
+        Self {
+            path: Some(path),
+            state,
+            changed: false,
+        }
+    }
+
+    /// Picks next of the `tracks` according to the rotation mode, `exclude` tracks are never
+    /// picked but keep their place in the history
+    pub fn pick(
+        &mut self,
+        mode: SoundRotation,
+        category: &str,
+        list_name: &str,
+        tracks: &[PathBuf],
+        exclude: &HashSet<PathBuf>,
+    ) -> Option<PathBuf> {
+        let mut rng = rand::thread_rng();
+        let history = self
+            .state
+            .histories
+            .entry(format!("{category}/{list_name}"))
+            .or_default();
+        let candidates = tracks.iter().filter(|x| !exclude.contains(*x));
+
+        let result = match mode {
+            SoundRotation::Random => candidates.choose(&mut rng).cloned(),
+            SoundRotation::ShuffleBag => {
+                // Tracks removed from the disk since the bag was filled are dropped
+                history.bag.retain(|x| tracks.contains(x));
+                if !history.bag.iter().any(|x| !exclude.contains(x)) {
+                    history.bag = tracks.to_vec();
+                    history.bag.shuffle(&mut rng);
+
+                    // The last track of the previous bag shouldn't open the new one
+                    let last = history
+                        .last_played
+                        .iter()
+                        .max_by_key(|(_, play)| **play)
+                        .map(|(path, _)| path);
+                    if history.bag.len() > 1 && history.bag.last() == last {
+                        let last_index = history.bag.len() - 1;
+                        history.bag.swap(0, last_index);
+                    }
+                }
+                let index = history.bag.iter().rposition(|x| !exclude.contains(x))?;
+                Some(history.bag.remove(index))
+            }
+            SoundRotation::LeastRecentlyPlayed => {
+                // Tracks that never played come first
+                let last_played =
+                    |x: &PathBuf| history.last_played.get(x).copied().unwrap_or_default();
+                let oldest = candidates.clone().map(last_played).min()?;
+                candidates
+                    .filter(|x| last_played(x) == oldest)
+                    .choose(&mut rng)
+                    .cloned()
+            }
+        }?;
+
+        self.state.plays += 1;
+        history.last_played.retain(|path, _| tracks.contains(path));
+        history.last_played.insert(result.clone(), self.state.plays);
+        self.changed = true;
+
+        Some(result)
+    }
+
+    /// Saves the history if anything was picked since the last save
+    pub fn save(&mut self) {
+        if !self.changed {
+            return;
+        }
+
+        if let Err(e) = self.write() {
+            error!("Failed to save sound rotation: {:#?}", e);
+        }
+        self.changed = false;
+    }
+
+    fn write(&self) -> Result<()> {
+        if let Some(path) = &self.path {
+            write(path, toml::to_string(&self.state)?)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn read_state(path: &Path) -> Result<RotationState> {
+    Ok(toml::from_str(read_to_string(path)?.as_str())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use super::Rotation;
+    use crate::SoundRotation;
+
+    fn tracks() -> Vec<PathBuf> {
+        ["a.mp3", "b.mp3", "c.mp3"].map(PathBuf::from).to_vec()
+    }
+
+    fn pick(rotation: &mut Rotation, mode: SoundRotation, exclude: &[&str]) -> PathBuf {
+        let exclude: HashSet<PathBuf> = exclude.iter().map(PathBuf::from).collect();
+        rotation
+            .pick(mode, "roll", "games", &tracks(), &exclude)
+            .unwrap()
+    }
+
+    #[test]
+    fn shuffle_bag_plays_every_track_before_refill() {
+        let mut rotation = Rotation::default();
+        for _ in 0..20 {
+            let mut round: Vec<PathBuf> = (0..3)
+                .map(|_| pick(&mut rotation, SoundRotation::ShuffleBag, &[]))
+                .collect();
+            let last = round.last().cloned();
+            round.sort();
+            assert_eq!(round, tracks());
+
+            // The refilled bag doesn't open with the track that closed the previous one
+            assert_ne!(
+                Some(pick(&mut rotation, SoundRotation::ShuffleBag, &[])),
+                last
+            );
+            pick(&mut rotation, SoundRotation::ShuffleBag, &[]);
+            pick(&mut rotation, SoundRotation::ShuffleBag, &[]);
+        }
+    }
+
+    #[test]
+    fn shuffle_bag_keeps_excluded_tracks() {
+        let mut rotation = Rotation::default();
+        let first = pick(&mut rotation, SoundRotation::ShuffleBag, &[]);
+        let rest: Vec<&str> = ["a.mp3", "b.mp3", "c.mp3"]
+            .into_iter()
+            .filter(|x| PathBuf::from(x) != first)
+            .collect();
+
+        // Excluding a track postpones it instead of dropping it from the bag
+        let second = pick(&mut rotation, SoundRotation::ShuffleBag, &rest[..1]);
+        assert_eq!(second, PathBuf::from(rest[1]));
+        let third = pick(&mut rotation, SoundRotation::ShuffleBag, &[]);
+        assert_eq!(third, PathBuf::from(rest[0]));
+    }
+
+    #[test]
+    fn least_recently_played_goes_by_play_order() {
+        let mut rotation = Rotation::default();
+        let mut played: Vec<PathBuf> = (0..3)
+            .map(|_| pick(&mut rotation, SoundRotation::LeastRecentlyPlayed, &[]))
+            .collect();
+        let order = played.clone();
+        played.sort();
+        assert_eq!(played, tracks());
+
+        // An excluded track stays the oldest and plays as soon as it's allowed
+        let oldest = order[0].to_str().unwrap();
+        assert_eq!(
+            pick(&mut rotation, SoundRotation::LeastRecentlyPlayed, &[oldest]),
+            order[1]
+        );
+        assert_eq!(
+            pick(&mut rotation, SoundRotation::LeastRecentlyPlayed, &[]),
+            order[0]
+        );
+        assert_eq!(
+            pick(&mut rotation, SoundRotation::LeastRecentlyPlayed, &[]),
+            order[2]
+        );
+    }
+}
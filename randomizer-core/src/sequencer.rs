@@ -0,0 +1,323 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
+    thread,
+};
+
+use log::{error, warn};
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender, WeakUnboundedSender},
+    RwLock,
+};
+
+use crate::{
+    audio_player::{Channel, Player, SyncCallback, SyncType},
+    event::{Event, Events, NowPlaying},
+    playlist::PlaylistEntries,
+    Result,
+};
+
+/// Stop sound and what happens to the music around it
+#[derive(Clone, Debug)]
+pub(crate) struct StopSound {
+    pub fade_in: u32,
+    /// Fade-out time of the ducked music after the stop sound, `None` stops the music at once
+    pub music_fade_out: Option<u32>,
+    pub path: PathBuf,
+}
+
+/// Picks a sound, sounds of the set are never picked
+pub(crate) type PickSound = Box<dyn FnMut(&HashSet<PathBuf>) -> Option<PathBuf> + Send>;
+
+/// Stop sound that is picked once the music fades out, so unplayable sounds can be skipped then
+pub(crate) struct QueuedStopSound {
+    pub fade_in: u32,
+    pub music_fade_out: Option<u32>,
+    pub pick: PickSound,
+}
+
+/// Messages to the sequencer, from audio callbacks and from the randomizer. Cues of audio
+/// callbacks carry the generation of the roll that set them up, a callback may fire after the
+/// next roll has started
+pub(crate) enum Cue {
+    /// Roll music has faded out
+    MusicFaded(u64),
+    /// Play the stop sound as soon as the music fades out
+    QueueStopSound(QueuedStopSound),
+    /// New roll has started with the given generation, queued sounds and cues of the previous
+    /// rolls are dropped
+    Reset(u64),
+    /// Stop sound has played to the end
+    StopSoundEnded(u64, Option<u32>),
+}
+
+/// Owns playback that goes on after the audio callbacks. Callbacks only send cues to its thread,
+/// so they never wait for the player lock on the audio thread
+pub(crate) struct Sequencer {
+    generation: AtomicU64,
+    sender: UnboundedSender<Cue>,
+}
+
+impl Sequencer {
+    /// Starts the sequencer thread, it stops when the player is dropped
+    pub fn spawn(
+        player: &Arc<RwLock<Player>>,
+        events: Events,
+        playlist_entries: Arc<PlaylistEntries>,
+    ) -> Self {
+        let (sender, receiver) = unbounded_channel();
+        let task = SequencerTask {
+            events,
+            generation: 0,
+            player: Arc::downgrade(player),
+            playlist_entries,
+            queued: None,
+            // Streams hold the senders of their callbacks, so a strong sender would keep the
+            // thread alive forever
+            sender: sender.downgrade(),
+        };
+        thread::spawn(move || task.run(receiver));
+
+        Self {
+            generation: AtomicU64::new(0),
+            sender,
+        }
+    }
+
+    /// Starts a new roll generation, cues of the previous rolls are ignored from now on
+    pub fn reset(&self) {
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        self.send(Cue::Reset(generation));
+    }
+
+    /// Generation of the current roll, for the cues of its callbacks
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    pub fn send(&self, cue: Cue) {
+        // The thread is gone only when the player is, nothing is left to sequence
+        let _ = self.sender.send(cue);
+    }
+
+    pub fn sender(&self) -> &UnboundedSender<Cue> {
+        &self.sender
+    }
+}
+
+struct SequencerTask {
+    events: Events,
+    generation: u64,
+    player: Weak<RwLock<Player>>,
+    playlist_entries: Arc<PlaylistEntries>,
+    queued: Option<QueuedStopSound>,
+    sender: WeakUnboundedSender<Cue>,
+}
+
+impl SequencerTask {
+    fn run(mut self, mut receiver: UnboundedReceiver<Cue>) {
+        while let Some(cue) = receiver.blocking_recv() {
+            let player = match self.player.upgrade() {
+                Some(player) => player,
+                None => break,
+            };
+
+            let result = self.handle(cue, &mut player.blocking_write());
+            if let Err(e) = result {
+                error!("{:#?}", e);
+            }
+        }
+    }
+
+    fn handle(&mut self, cue: Cue, player: &mut Player) -> Result<()> {
+        match cue {
+            // Leftovers of an earlier roll, its sounds are stopped already
+            Cue::MusicFaded(generation) | Cue::StopSoundEnded(generation, _)
+                if generation != self.generation => {}
+            Cue::MusicFaded(_) => {
+                self.events.emit(Event::FadeDone);
+                if let Some(queued) = self.queued.take() {
+                    if let Some(sender) = self.sender.upgrade() {
+                        let generation = self.generation;
+                        // The music is over already, the sequence simply ends without the sound
+                        match play_first_playable(queued.pick, |path| {
+                            let sound = StopSound {
+                                fade_in: queued.fade_in,
+                                music_fade_out: queued.music_fade_out,
+                                path: path.to_path_buf(),
+                            };
+                            play_stop_sound(player, &sound, &sender, generation)
+                        }) {
+                            Ok(Some(path)) => self.events.emit(NowPlaying::event(
+                                "stop",
+                                &path,
+                                &self.playlist_entries,
+                            )),
+                            Ok(None) => (),
+                            Err(e) => error!("Failed to play stop sound: {:#?}", e),
+                        }
+                    }
+                }
+            }
+            Cue::QueueStopSound(sound) => self.queued = Some(sound),
+            Cue::Reset(generation) => {
+                self.generation = generation;
+                self.queued = None;
+            }
+            Cue::StopSoundEnded(_, music_fade_out) => {
+                self.events.emit(Event::StopSoundEnded);
+                player.stop(Channel::Stinger);
+                match music_fade_out {
+                    Some(time) => {
+                        if let Some(sender) = self.sender.upgrade() {
+                            fade_out_music(player, time, &sender, self.generation)?;
+                        }
+                    }
+                    None => player.stop(Channel::Music),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Plays sounds given by `pick` until one of them plays and returns it, `None` if no sound is
+/// left. Unplayable sounds are logged and passed back to `pick` to be excluded, other errors
+/// stop the attempts
+pub(crate) fn play_first_playable<T: AsRef<Path>>(
+    mut pick: impl FnMut(&HashSet<PathBuf>) -> Option<T>,
+    mut play: impl FnMut(&T) -> Result<()>,
+) -> Result<Option<T>> {
+    let mut unplayable = HashSet::new();
+    while let Some(sound) = pick(&unplayable) {
+        match play(&sound) {
+            Ok(()) => return Ok(Some(sound)),
+            Err(e) if e.is_recoverable() => {
+                let path = sound.as_ref();
+                warn!("Skipping unplayable sound {}: {:#?}", path.display(), e);
+                unplayable.insert(path.to_path_buf());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Plays the stop sound on the stinger channel, the sequencer is notified when it ends
+pub(crate) fn play_stop_sound(
+    player: &mut Player,
+    sound: &StopSound,
+    sender: &UnboundedSender<Cue>,
+    generation: u64,
+) -> Result<()> {
+    let music_fade_out = sound.music_fade_out;
+    let mut track = player.create_stream(&sound.path, None)?;
+    track.set_sync(
+        SyncType::End,
+        callback(sender.clone(), move || {
+            Cue::StopSoundEnded(generation, music_fade_out)
+        }),
+    )?;
+    player.play(Channel::Stinger, track, sound.fade_in)?;
+
+    Ok(())
+}
+
+/// Fades out the music, the sequencer is notified when it's done. Returns `false` if no music
+/// is playing
+pub(crate) fn fade_out_music(
+    player: &mut Player,
+    time: u32,
+    sender: &UnboundedSender<Cue>,
+    generation: u64,
+) -> Result<bool> {
+    if !player.set_sync(
+        Channel::Music,
+        SyncType::Slide,
+        callback(sender.clone(), move || Cue::MusicFaded(generation)),
+    )? {
+        return Ok(false);
+    }
+    player.fade_out(Channel::Music, time)?;
+
+    Ok(true)
+}
+
+/// Sync callback that sends a cue made by `cue` every time it's called
+fn callback(
+    sender: UnboundedSender<Cue>,
+    cue: impl Fn() -> Cue + Send + Sync + 'static,
+) -> SyncCallback {
+    Box::new(move || {
+        let _ = sender.send(cue());
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc, thread, time::Duration};
+
+    use tokio::sync::{broadcast::Receiver, RwLock};
+
+    use super::{Cue, QueuedStopSound, Sequencer};
+    use crate::{
+        audio_player::{AudioCall, NullBackend, Player},
+        event::{Event, Events},
+    };
+
+    fn sequencer() -> (NullBackend, Arc<RwLock<Player>>, Sequencer, Receiver<Event>) {
+        let backend = NullBackend::new();
+        let player = Arc::new(RwLock::new(Player::new(Box::new(backend.clone()))));
+        let events = Events::new();
+        let receiver = events.subscribe();
+        let sequencer = Sequencer::spawn(&player, events, Default::default());
+
+        (backend, player, sequencer, receiver)
+    }
+
+    fn received(receiver: &mut Receiver<Event>) -> Vec<&'static str> {
+        // The sequencer handles cues on its own thread
+        thread::sleep(Duration::from_millis(100));
+        let mut result = vec![];
+        while let Ok(event) = receiver.try_recv() {
+            result.push(event.name());
+        }
+        result
+    }
+
+    #[test]
+    fn drops_cues_of_previous_rolls() {
+        let (backend, _player, sequencer, mut receiver) = sequencer();
+        let path = PathBuf::from("stop.wav");
+
+        sequencer.reset();
+        let stale = sequencer.generation();
+        sequencer.reset();
+        assert_eq!(sequencer.generation(), stale + 1);
+
+        let picked = path.clone();
+        sequencer.send(Cue::QueueStopSound(QueuedStopSound {
+            fade_in: 0,
+            music_fade_out: None,
+            pick: Box::new(move |exclude| Some(picked.clone()).filter(|x| !exclude.contains(x))),
+        }));
+        sequencer.send(Cue::MusicFaded(stale));
+        sequencer.send(Cue::StopSoundEnded(stale, None));
+        assert!(received(&mut receiver).is_empty());
+        assert!(!backend.calls().contains(&AudioCall::Play(path.clone())));
+
+        // The queued sound survives stale cues and plays after the fade of the current roll
+        sequencer.send(Cue::MusicFaded(sequencer.generation()));
+        assert_eq!(
+            received(&mut receiver),
+            vec!["fade-done", "now-playing", "stop-sound-ended"]
+        );
+        assert!(backend.calls().contains(&AudioCall::Play(path)));
+    }
+}
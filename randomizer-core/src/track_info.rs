@@ -0,0 +1,117 @@
+use std::{
+    ffi::OsString,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use lofty::{AudioFile, ItemKey, TaggedFile, TaggedFileExt};
+use log::error;
+use serde::Deserialize;
+
+use crate::{audio_player::LoopSection, Result};
+
+const SIDECAR_EXTENSION: &str = "toml";
+const STOP_AT_TAG: &str = "STOP_AT";
+/// Loop tags of game music and RPG Maker, values are in samples
+const LOOP_START_TAG: &str = "LOOPSTART";
+const LOOP_LENGTH_TAG: &str = "LOOPLENGTH";
+const LOOP_END_TAG: &str = "LOOPEND";
+
+/// Cue and loop points of a track, read from a sidecar file (`track.mp3.toml`) or from the file tags
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub(crate) struct TrackInfo {
+    /// Time in seconds the repeated part of the track ends at, the end of the file by default
+    pub loop_end: Option<f64>,
+    /// Time in seconds the repeated part of the track starts at, the part before it is an intro
+    /// that plays once
+    pub loop_start: Option<f64>,
+    /// Time in seconds the wheel should stop at
+    pub stop_at: Option<f64>,
+}
+
+impl TrackInfo {
+    /// Reads cue points, sidecar file takes precedence over the tags
+    pub fn load(path: &Path) -> Self {
+        Self::load_with(path, || Ok(Self::from_tags(&lofty::read_from_path(path)?)))
+    }
+
+    /// Like `load`, but takes the tags already read from the file, `None` if they couldn't be
+    /// read
+    pub fn with_tags(path: &Path, file: Option<&TaggedFile>) -> Self {
+        Self::load_with(path, || Ok(file.map(Self::from_tags).unwrap_or_default()))
+    }
+
+    /// Returns the repeated part of the track, the whole track if it has no loop points
+    pub fn loop_section(&self) -> LoopSection {
+        let start = self.loop_start.unwrap_or_default().max(0.0);
+        LoopSection {
+            start,
+            end: self.loop_end.filter(|end| *end > start),
+        }
+    }
+
+    fn load_with(path: &Path, from_tags: impl FnOnce() -> Result<Self>) -> Self {
+        let sidecar_path = sidecar_path(path);
+        let result = if sidecar_path.is_file() {
+            TrackInfo::from_sidecar(&sidecar_path)
+        } else {
+            from_tags()
+        };
+
+        result.unwrap_or_else(|e| {
+            error!(
+                "Failed to read cue and loop points for {}: {:#?}",
+                path.display(),
+                e
+            );
+            Self::default()
+        })
+    }
+
+    fn from_sidecar(path: &Path) -> Result<Self> {
+        Ok(toml::from_str(read_to_string(path)?.as_str())?)
+    }
+
+    fn from_tags(file: &TaggedFile) -> Self {
+        let stop_at = tag_number(file, STOP_AT_TAG);
+
+        // Loop points are in samples, the end is given either directly or as loop length
+        let sample_rate = file.properties().sample_rate().map(f64::from);
+        let to_seconds = |samples: f64| sample_rate.map(|rate| samples / rate);
+        let loop_start = tag_number(file, LOOP_START_TAG);
+        let loop_end = tag_number(file, LOOP_END_TAG).or_else(|| {
+            tag_number(file, LOOP_LENGTH_TAG).map(|length| loop_start.unwrap_or_default() + length)
+        });
+
+        Self {
+            loop_end: loop_end.and_then(to_seconds),
+            loop_start: loop_start.and_then(to_seconds),
+            stop_at,
+        }
+    }
+}
+
+/// Returns number from the custom tag with the given name
+fn tag_number(file: &TaggedFile, name: &str) -> Option<f64> {
+    file.tags()
+        .iter()
+        .flat_map(|tag| tag.items())
+        .find(|item| match item.key() {
+            // MP4 freeform atoms are prefixed with their mean, e.g. "----:com.apple.iTunes:"
+            ItemKey::Unknown(key) => key
+                .rsplit(':')
+                .next()
+                .is_some_and(|key| key.eq_ignore_ascii_case(name)),
+            _ => false,
+        })
+        .and_then(|item| item.value().text())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().map(OsString::from).unwrap_or_default();
+    file_name.push(".");
+    file_name.push(SIDECAR_EXTENSION);
+    path.with_file_name(file_name)
+}
@@ -10,11 +10,13 @@ fn main() {
     println!("cargo:rerun-if-changed=../extra/plugins/*");
 
     let profile = env::var("PROFILE").expect("Failed to get profile");
-    copy_to_output(
-        &["../extra/bass.dll", "../extra/data", "../extra/plugins"],
-        &profile,
-    )
-    .expect("Could not copy extra files");
+    // BASS library and its plugins aren't needed with the pure-Rust audio backend
+    let paths: &[&str] = if env::var_os("CARGO_FEATURE_RODIO").is_some() {
+        &["../extra/data"]
+    } else {
+        &["../extra/bass.dll", "../extra/data", "../extra/plugins"]
+    };
+    copy_to_output(paths, &profile).expect("Could not copy extra files");
 }
 
 fn copy_to_output(paths: &[&str], build_type: &str) -> Result<(), Box<dyn Error>> {
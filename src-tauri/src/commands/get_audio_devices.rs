@@ -1,9 +1,12 @@
 use anyhow::Context;
-use randomizer_core::list_audio_devices;
 
-use crate::error::Result;
+use crate::{error::Result, AppState};
 
 #[tauri::command]
-pub fn get_audio_devices() -> Result<Vec<(String, String)>> {
-    Ok(list_audio_devices().with_context(|| "Failed to get list of audio devices")?)
+pub fn get_audio_devices(state: tauri::State<'_, AppState>) -> Result<Vec<(String, String)>> {
+    Ok(state
+        .randomizer
+        .blocking_read()
+        .audio_devices()
+        .with_context(|| "Failed to get list of audio devices")?)
 }
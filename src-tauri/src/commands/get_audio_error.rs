@@ -0,0 +1,10 @@
+use crate::AppState;
+
+#[tauri::command]
+pub fn get_audio_error(state: tauri::State<'_, AppState>) -> Option<String> {
+    state
+        .randomizer
+        .blocking_read()
+        .audio_error()
+        .map(str::to_string)
+}
@@ -1,15 +1,21 @@
 mod get_audio_devices;
+mod get_audio_error;
 mod get_config;
 mod lists;
 mod random_bg;
 mod roll;
 mod set_config;
+mod sound_library;
 mod stop;
+mod wake;
 
 pub use get_audio_devices::get_audio_devices;
+pub use get_audio_error::get_audio_error;
 pub use get_config::get_config;
 pub use lists::lists;
 pub use random_bg::random_bg;
 pub use roll::roll;
 pub use set_config::set_config;
+pub use sound_library::sound_library;
 pub use stop::stop;
+pub use wake::wake;
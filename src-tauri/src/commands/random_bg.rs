@@ -1,16 +1,17 @@
 use anyhow::Context;
+use randomizer_core::Event;
 
-use crate::error::Result;
+use crate::{error::Result, AppState};
 
+/// Picks the next background of the list, it's sent with the `background` event, so the
+/// overlay gets it too
 #[tauri::command]
-pub fn random_bg(list_name: &str) -> Result<Option<(String, String)>> {
-    if let Some(file_path) = randomizer_core::random_bg(list_name)
-        .with_context(|| "Failed to select random background file")?
-    {
-        let mime_type = mime_guess::from_path(file_path.as_str()).first_or_octet_stream();
+pub fn random_bg(list_name: &str, state: tauri::State<'_, AppState>) -> Result<()> {
+    let randomizer = state.randomizer.blocking_read();
+    let file_path = randomizer
+        .random_bg(list_name)
+        .with_context(|| "Failed to select random background file")?;
+    randomizer.events().emit(Event::Background(file_path));
 
-        return Ok(Some((file_path, mime_type.essence_str().to_string())));
-    }
-
-    Ok(None)
+    Ok(())
 }
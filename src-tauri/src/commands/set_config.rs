@@ -1,13 +1,25 @@
 use anyhow::Context;
 use randomizer_core::Config;
+use tauri::AppHandle;
 
 use crate::{error::Result, AppState};
 
 #[tauri::command]
-pub fn set_config(config: Config, state: tauri::State<'_, AppState>) -> Result<()> {
-    Ok(state
-        .randomizer
-        .blocking_write()
-        .set_config(config)
-        .with_context(|| "Failed to apply new config")?)
+pub fn set_config(
+    app_handle: AppHandle,
+    config: Config,
+    state: tauri::State<'_, AppState>,
+) -> Result<()> {
+    // The lock is released before restarting services, their threads may wait for it
+    let (config, events) = {
+        let mut randomizer = state.randomizer.blocking_write();
+        randomizer
+            .set_config(config)
+            .with_context(|| "Failed to apply new config")?;
+        (randomizer.config().clone(), randomizer.events().clone())
+    };
+    state.osc.apply(&app_handle, &config, &events);
+    state.overlay.apply(&config, &events);
+
+    Ok(())
 }
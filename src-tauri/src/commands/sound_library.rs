@@ -0,0 +1,8 @@
+use randomizer_core::SoundLibrary;
+
+use crate::{error::Result, AppState};
+
+#[tauri::command]
+pub async fn sound_library(state: tauri::State<'_, AppState>) -> Result<SoundLibrary> {
+    Ok(state.randomizer.read().await.sound_library())
+}
@@ -0,0 +1,6 @@
+use crate::AppState;
+
+#[tauri::command]
+pub fn wake(list_name: &str, state: tauri::State<AppState>) {
+    state.randomizer.blocking_read().wake(list_name);
+}
@@ -0,0 +1,268 @@
+use std::{
+    error::Error,
+    fs::{File, Metadata},
+    io::{Read, Seek, SeekFrom},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use httpdate::HttpDate;
+use log::error;
+use tauri::{
+    http::{
+        header::{
+            HeaderMap, ACCEPT_RANGES, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_RANGE, ETAG,
+            IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE,
+        },
+        HttpRange, Request, Response, ResponseBuilder,
+    },
+    AppHandle,
+};
+
+use randomizer_core::{data_file_path, resized_image};
+
+/// Largest body of a partial response. Tauri needs the whole body at once, so clients that
+/// request ranges, e.g. media players, get big files piece by piece, requesting the next range
+/// as they go. Requests without a range get the whole file
+const MAX_RANGE_LENGTH: u64 = 4 * 1024 * 1024;
+
+pub fn data_protocol_handler(
+    _app_handle: &AppHandle,
+    request: &Request,
+) -> Result<Response, Box<dyn Error>> {
+    let not_found = ResponseBuilder::new().status(404).body(Vec::new());
+    if request.method() != "GET" {
+        return not_found;
+    }
+    let (path, query) = request.uri().split_once('?').unwrap_or((request.uri(), ""));
+    let file_path = match path
+        .strip_prefix("data://localhost/")
+        .and_then(data_file_path)
+    {
+        Some(file_path) => file_path,
+        None => return not_found,
+    };
+    let file_path = match requested_width(query) {
+        Some(width) => resized_image(&file_path, width).unwrap_or_else(|e| {
+            error!("Failed to resize {}: {:#?}", file_path.display(), e);
+            file_path
+        }),
+        None => file_path,
+    };
+
+    let mut file = File::open(&file_path)?;
+    let metadata = file.metadata()?;
+    let size = metadata.len();
+    let modified = metadata.modified().ok();
+    let etag = entity_tag(&metadata);
+
+    let mime_type = mime_guess::from_path(&file_path).first_or_octet_stream();
+    // Files may change while the app runs, e.g. a new background, so the cached copy is
+    // always revalidated
+    let mut response = ResponseBuilder::new()
+        .mimetype(mime_type.essence_str())
+        .header(ACCEPT_RANGES, "bytes")
+        .header(CACHE_CONTROL, "no-cache")
+        .header(ETAG, etag.as_str());
+    if let Some(modified) = modified {
+        response = response.header(LAST_MODIFIED, httpdate::fmt_http_date(modified));
+    }
+
+    if is_not_modified(request.headers(), &etag, modified) {
+        return response.status(304).body(Vec::new());
+    }
+
+    match requested_range(request.headers(), &etag, modified, size) {
+        Ok(Some(range)) => {
+            let length = range.length.min(MAX_RANGE_LENGTH);
+            file.seek(SeekFrom::Start(range.start))?;
+            response
+                .status(206)
+                .header(
+                    CONTENT_RANGE,
+                    format!("bytes {}-{}/{size}", range.start, range.start + length - 1),
+                )
+                .header(CONTENT_LENGTH, length.to_string())
+                .body(read_body(file, length)?)
+        }
+        Ok(None) => response
+            .status(200)
+            .header(CONTENT_LENGTH, size.to_string())
+            .body(read_body(file, size)?),
+        Err(()) => response
+            .status(416)
+            .header(CONTENT_RANGE, format!("bytes */{size}"))
+            .body(Vec::new()),
+    }
+}
+
+/// Parses the `w` query parameter, the width of a downscaled image variant
+fn requested_width(query: &str) -> Option<u32> {
+    query
+        .split('&')
+        .find_map(|x| x.strip_prefix("w="))
+        .and_then(|x| x.parse().ok())
+}
+
+/// Entity tag built from the size and modification time of the file, it changes when the file
+/// is replaced or edited
+pub(crate) fn entity_tag(metadata: &Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!("\"{:x}-{:x}\"", metadata.len(), modified.as_nanos())
+}
+
+/// Checks the conditional headers, `If-None-Match` takes precedence over `If-Modified-Since`
+pub(crate) fn is_not_modified(
+    headers: &HeaderMap,
+    etag: &str,
+    modified: Option<SystemTime>,
+) -> bool {
+    if let Some(value) = headers.get(IF_NONE_MATCH).and_then(|x| x.to_str().ok()) {
+        return value
+            .split(',')
+            .map(|x| x.trim().trim_start_matches("W/"))
+            .any(|x| x == "*" || x == etag);
+    }
+
+    match (
+        modified,
+        headers
+            .get(IF_MODIFIED_SINCE)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| httpdate::parse_http_date(x).ok()),
+    ) {
+        // HTTP dates have a precision of a second
+        (Some(modified), Some(since)) => SystemTime::from(HttpDate::from(modified)) <= since,
+        _ => false,
+    }
+}
+
+/// Returns the requested byte range, `None` for the whole file. Only the first one of multiple
+/// ranges is served. `Err` means the range is outside the file
+pub(crate) fn requested_range(
+    headers: &HeaderMap,
+    etag: &str,
+    modified: Option<SystemTime>,
+    size: u64,
+) -> Result<Option<HttpRange>, ()> {
+    let range = match headers.get(RANGE).and_then(|x| x.to_str().ok()) {
+        Some(range) => range,
+        None => return Ok(None),
+    };
+
+    // A range of a stale copy is useless, the whole file is sent instead
+    if let Some(value) = headers.get(IF_RANGE).and_then(|x| x.to_str().ok()) {
+        let last_modified = modified.map(httpdate::fmt_http_date);
+        if value != etag && Some(value) != last_modified.as_deref() {
+            return Ok(None);
+        }
+    }
+
+    HttpRange::parse(range, size)
+        .map(|ranges| ranges.first().copied())
+        .map_err(|_| ())
+}
+
+/// Reads `length` bytes from the current position of the file
+fn read_body(file: File, length: u64) -> std::io::Result<Vec<u8>> {
+    let mut body = Vec::with_capacity(length as usize);
+    file.take(length).read_to_end(&mut body)?;
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use tauri::http::header::{
+        HeaderMap, HeaderName, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, RANGE,
+    };
+
+    use super::{is_not_modified, requested_range};
+
+    const ETAG: &str = "\"400-1\"";
+
+    fn modified() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_600_000_000)
+    }
+
+    fn headers(pairs: &[(HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    fn range(headers: &HeaderMap) -> Result<Option<(u64, u64)>, ()> {
+        requested_range(headers, ETAG, Some(modified()), 1024)
+            .map(|range| range.map(|x| (x.start, x.length)))
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(range(&headers(&[])), Ok(None));
+        assert_eq!(range(&headers(&[(RANGE, "bytes=0-")])), Ok(Some((0, 1024))));
+        assert_eq!(
+            range(&headers(&[(RANGE, "bytes=100-199")])),
+            Ok(Some((100, 100)))
+        );
+        assert_eq!(
+            range(&headers(&[(RANGE, "bytes=-24")])),
+            Ok(Some((1000, 24)))
+        );
+
+        // Only the first of multiple ranges is served
+        assert_eq!(
+            range(&headers(&[(RANGE, "bytes=0-9, 20-29")])),
+            Ok(Some((0, 10)))
+        );
+        assert_eq!(range(&headers(&[(RANGE, "bytes=2000-")])), Err(()));
+    }
+
+    #[test]
+    fn ignores_ranges_of_stale_copies() {
+        let date = httpdate::fmt_http_date(modified());
+        for value in [ETAG, date.as_str()] {
+            assert_eq!(
+                range(&headers(&[(RANGE, "bytes=100-199"), (IF_RANGE, value)])),
+                Ok(Some((100, 100)))
+            );
+        }
+
+        let date = httpdate::fmt_http_date(modified() - Duration::from_secs(60));
+        for value in ["\"400-2\"", date.as_str()] {
+            assert_eq!(
+                range(&headers(&[(RANGE, "bytes=100-199"), (IF_RANGE, value)])),
+                Ok(None)
+            );
+        }
+    }
+
+    #[test]
+    fn checks_conditional_headers() {
+        let not_modified =
+            |pairs: &[(HeaderName, &str)]| is_not_modified(&headers(pairs), ETAG, Some(modified()));
+        assert!(!not_modified(&[]));
+
+        assert!(not_modified(&[(IF_NONE_MATCH, ETAG)]));
+        assert!(not_modified(&[(IF_NONE_MATCH, "W/\"400-1\"")]));
+        assert!(not_modified(&[(IF_NONE_MATCH, "\"a\", \"400-1\"")]));
+        assert!(not_modified(&[(IF_NONE_MATCH, "*")]));
+        assert!(!not_modified(&[(IF_NONE_MATCH, "\"400-2\"")]));
+
+        // Dates are compared with a precision of a second
+        let same = httpdate::fmt_http_date(modified() + Duration::from_millis(500));
+        let earlier = httpdate::fmt_http_date(modified() - Duration::from_secs(1));
+        assert!(not_modified(&[(IF_MODIFIED_SINCE, same.as_str())]));
+        assert!(!not_modified(&[(IF_MODIFIED_SINCE, earlier.as_str())]));
+
+        // The entity tag takes precedence over the date
+        assert!(!not_modified(&[
+            (IF_NONE_MATCH, "\"400-2\""),
+            (IF_MODIFIED_SINCE, same.as_str())
+        ]));
+    }
+}
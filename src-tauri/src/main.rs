@@ -4,27 +4,37 @@
 )]
 
 mod commands;
+mod data_protocol;
 mod error;
-
-use std::{error::Error, fs::read};
+mod osc;
+mod overlay;
 
 use log::error;
-use tauri::{
-    http::{Request, Response, ResponseBuilder},
-    AppHandle, Manager, RunEvent,
-};
-use tokio::sync::RwLock;
+use tauri::{AppHandle, Manager, RunEvent};
+use tokio::sync::{broadcast::error::RecvError, RwLock};
 
-use randomizer_core::{data_path, Randomizer};
+use randomizer_core::Randomizer;
 
-use crate::commands::{get_audio_devices, get_config, lists, random_bg, roll, set_config, stop};
+use crate::{
+    commands::{
+        get_audio_devices, get_audio_error, get_config, lists, random_bg, roll, set_config,
+        sound_library, stop, wake,
+    },
+    data_protocol::data_protocol_handler,
+    osc::Osc,
+    overlay::Overlay,
+};
 
 pub struct AppState {
+    pub osc: Osc,
+    pub overlay: Overlay,
     pub randomizer: RwLock<Randomizer>,
 }
 
 fn main() -> anyhow::Result<()> {
     let state = AppState {
+        osc: Osc::new(),
+        overlay: Overlay::new(),
         randomizer: RwLock::new(Randomizer::new()?),
     };
 
@@ -32,12 +42,15 @@ fn main() -> anyhow::Result<()> {
         .manage(state)
         .invoke_handler(tauri::generate_handler![
             get_audio_devices,
+            get_audio_error,
             get_config,
             lists,
             random_bg,
             roll,
             set_config,
-            stop
+            sound_library,
+            stop,
+            wake
         ])
         .register_uri_scheme_protocol("data", data_protocol_handler)
         .build(tauri::generate_context!())
@@ -47,42 +60,52 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn data_protocol_handler(
-    _app_handle: &AppHandle,
-    request: &Request,
-) -> Result<Response, Box<dyn Error>> {
-    let not_found = ResponseBuilder::new().status(404).body(Vec::new());
-    if request.method() != "GET" {
-        return not_found;
-    }
-    let response = ResponseBuilder::new();
-    match request.uri().strip_prefix("data://localhost/") {
-        Some(path) => {
-            let mut file_path = data_path();
-            file_path.push(
-                percent_encoding::percent_decode(path.as_bytes())
-                    .decode_utf8_lossy()
-                    .to_string(),
-            );
-            if !file_path.is_file() {
-                return not_found;
-            }
+fn forward_events(app_handle: AppHandle) {
+    let mut receiver = app_handle
+        .state::<AppState>()
+        .randomizer
+        .blocking_read()
+        .events()
+        .subscribe();
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
 
-            let mime_type = mime_guess::from_path(&file_path).first_or_octet_stream();
-            response
-                .mimetype(mime_type.essence_str())
-                .status(200)
-                .body(read(file_path)?)
+            app_handle.state::<AppState>().overlay.track(&event).await;
+            if let Err(e) = app_handle.emit_all(event.name(), &event) {
+                error!("Failed to emit '{}' event: {:#?}", event.name(), e);
+            }
         }
-        None => not_found,
-    }
+    });
 }
 
 fn run_event_handler(app_handle: &AppHandle, event: RunEvent) {
+    if let tauri::RunEvent::Exit = event {
+        app_handle
+            .state::<AppState>()
+            .randomizer
+            .blocking_read()
+            .save_rotation();
+    }
+
     if let tauri::RunEvent::Ready = event {
+        forward_events(app_handle.clone());
+
         let state = app_handle.state::<AppState>();
+        let (config, events) = {
+            let randomizer = state.randomizer.blocking_read();
+            (randomizer.config().clone(), randomizer.events().clone())
+        };
+
+        state.osc.apply(app_handle, &config, &events);
+        state.overlay.apply(&config, &events);
 
-        if state.randomizer.blocking_read().config().start_fullscreen() {
+        if config.start_fullscreen() {
             if let Some(window) = app_handle.get_window("main") {
                 if let Err(e) = window.set_fullscreen(true) {
                     error!("Failed to enable full screen mode: {:#?}", e);
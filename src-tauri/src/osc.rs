@@ -0,0 +1,129 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use log::{error, info};
+use tauri::{AppHandle, Manager};
+use tokio::sync::{broadcast::error::TryRecvError, Mutex};
+
+use randomizer_core::{Config, Events, OscCommand, OscSocket};
+
+use crate::AppState;
+
+struct Listener {
+    listen_address: String,
+    send_address: String,
+    stopped: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+/// OSC listener that maps incoming messages to randomizer operations and reports state changes back
+pub struct Osc {
+    listener: Mutex<Option<Listener>>,
+}
+
+impl Osc {
+    pub fn new() -> Self {
+        Self {
+            listener: Mutex::new(None),
+        }
+    }
+
+    /// Starts, stops or restarts the listener according to the new config
+    pub fn apply(&self, app_handle: &AppHandle, config: &Config, events: &Events) {
+        let mut listener = self.listener.blocking_lock();
+        if config.osc_enabled()
+            && matches!(&*listener, Some(x) if &x.listen_address == config.osc_listen_address()
+                && &x.send_address == config.osc_send_address())
+        {
+            return;
+        }
+
+        if let Some(old) = listener.take() {
+            old.stopped.store(true, Ordering::Relaxed);
+            if old.thread.join().is_err() {
+                error!("OSC listener thread panicked");
+            }
+        }
+
+        if !config.osc_enabled() {
+            return;
+        }
+
+        let socket = match OscSocket::bind(config.osc_listen_address(), config.osc_send_address()) {
+            Ok(socket) => socket,
+            Err(e) => {
+                error!("Failed to start OSC listener: {:#?}", e);
+                return;
+            }
+        };
+        match socket.local_addr() {
+            Ok(address) => info!("OSC listener started on {address}"),
+            Err(e) => error!("{:#?}", e),
+        }
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let app_handle = app_handle.clone();
+            let mut receiver = events.subscribe();
+            let stopped = stopped.clone();
+            thread::spawn(move || {
+                while !stopped.load(Ordering::Relaxed) {
+                    match socket.receive() {
+                        Ok(commands) => {
+                            for command in commands {
+                                execute(&app_handle, command);
+                            }
+                        }
+                        Err(e) => error!("Failed to receive OSC message: {:#?}", e),
+                    }
+
+                    loop {
+                        match receiver.try_recv() {
+                            Ok(event) => {
+                                if let Err(e) = socket.send_event(&event) {
+                                    error!("Failed to send OSC message: {:#?}", e);
+                                }
+                            }
+                            Err(TryRecvError::Lagged(_)) => continue,
+                            Err(TryRecvError::Empty) => break,
+                            Err(TryRecvError::Closed) => return,
+                        }
+                    }
+                }
+            })
+        };
+
+        *listener = Some(Listener {
+            listen_address: config.osc_listen_address().clone(),
+            send_address: config.osc_send_address().clone(),
+            stopped,
+            thread,
+        });
+    }
+}
+
+fn execute(app_handle: &AppHandle, command: OscCommand) {
+    let state = app_handle.state::<AppState>();
+    match command {
+        OscCommand::Roll(list_name) => {
+            let app_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                let state = app_handle.state::<AppState>();
+                if let Err(e) = state.randomizer.read().await.roll(&list_name).await {
+                    error!("Failed to roll wheel from OSC: {:#?}", e);
+                }
+            });
+        }
+        OscCommand::Stop => state.randomizer.blocking_read().stop_roll(),
+        OscCommand::Volume(volume) => {
+            if let Err(e) = state.randomizer.blocking_read().set_volume(volume) {
+                error!("Failed to set volume from OSC: {:#?}", e);
+            }
+        }
+    }
+}
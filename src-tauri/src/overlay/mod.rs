@@ -0,0 +1,311 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fs::File,
+    io::SeekFrom,
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use hyper::{
+    body::Sender,
+    header::{
+        HeaderMap, ACCEPT_RANGES, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+        LAST_MODIFIED,
+    },
+    http::response::Builder,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use log::{error, info};
+use tokio::{
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::{broadcast::error::RecvError, oneshot, Mutex, RwLock},
+};
+
+use randomizer_core::{data_file_path, Config, Event, Events};
+
+use crate::data_protocol::{entity_tag, is_not_modified, requested_range};
+
+const INDEX_HTML: &str = include_str!("index.html");
+const REPLAYED_EVENTS: [&str; 4] = ["config", "background", "wheel-list", "winner"];
+/// Size of the pieces files are streamed in
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+type Snapshot = Arc<RwLock<HashMap<&'static str, Event>>>;
+
+/// Local HTTP server with a page that mirrors the wheel, meant to be captured by OBS as a browser source
+pub struct Overlay {
+    running: Mutex<Option<(u16, oneshot::Sender<()>)>>,
+    /// Latest events that are replayed to newly connected clients
+    snapshot: Snapshot,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self {
+            running: Mutex::new(None),
+            snapshot: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub async fn track(&self, event: &Event) {
+        let mut snapshot = self.snapshot.write().await;
+        if let Event::WheelList(_) = event {
+            snapshot.remove("winner");
+        }
+        snapshot.insert(event.name(), event.clone());
+    }
+
+    /// Starts, stops or restarts the server according to the new config
+    pub fn apply(&self, config: &Config, events: &Events) {
+        self.snapshot
+            .blocking_write()
+            .insert("config", Event::Config(Box::new(config.clone())));
+
+        let port = config.overlay_port();
+        let mut running = self.running.blocking_lock();
+        if config.overlay_enabled() && matches!(*running, Some((p, _)) if p == port) {
+            return;
+        }
+
+        if let Some((_, shutdown)) = running.take() {
+            let _ = shutdown.send(());
+        }
+
+        if !config.overlay_enabled() {
+            return;
+        }
+
+        // Bound here, so a failed start isn't recorded as running and the next apply retries it
+        let address = SocketAddr::from(([127, 0, 0, 1], port));
+        let listener = match TcpListener::bind(address).and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            Ok(listener)
+        }) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to start overlay server on {address}: {:#?}", e);
+                return;
+            }
+        };
+
+        let (shutdown, shutdown_receiver) = oneshot::channel::<()>();
+        let events = events.clone();
+        let snapshot = self.snapshot.clone();
+        tauri::async_runtime::spawn(async move {
+            let server = match Server::from_tcp(listener) {
+                Ok(server) => server,
+                Err(e) => {
+                    error!("Failed to start overlay server on {address}: {:#?}", e);
+                    return;
+                }
+            };
+
+            let make_service = make_service_fn(move |_| {
+                let events = events.clone();
+                let snapshot = snapshot.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request| {
+                        handle_request(request, events.clone(), snapshot.clone())
+                    }))
+                }
+            });
+
+            info!("Overlay is available at http://{address}/");
+            if let Err(e) = server
+                .serve(make_service)
+                .with_graceful_shutdown(async {
+                    shutdown_receiver.await.ok();
+                })
+                .await
+            {
+                error!("Overlay server error: {:#?}", e);
+            }
+        });
+
+        *running = Some((port, shutdown));
+    }
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    events: Events,
+    snapshot: Snapshot,
+) -> Result<Response<Body>, Infallible> {
+    if request.method() != Method::GET {
+        return Ok(status_response(StatusCode::METHOD_NOT_ALLOWED));
+    }
+
+    let path = request.uri().path();
+    let response = match path {
+        "/" => response("text/html; charset=utf-8", Body::from(INDEX_HTML)),
+        "/events" => {
+            let snapshot = snapshot.read().await;
+            event_stream(
+                REPLAYED_EVENTS
+                    .iter()
+                    .filter_map(|name| snapshot.get(name).cloned())
+                    .collect(),
+                &events,
+            )
+        }
+        _ => match path.strip_prefix("/data/").and_then(data_file_path) {
+            Some(file_path) => file_response(request.headers(), file_path),
+            None => status_response(StatusCode::NOT_FOUND),
+        },
+    };
+
+    Ok(response)
+}
+
+fn event_stream(replayed: Vec<Event>, events: &Events) -> Response<Body> {
+    let mut receiver = events.subscribe();
+    let (mut sender, body) = Body::channel();
+
+    tauri::async_runtime::spawn(async move {
+        let mut replayed = replayed.into_iter();
+        loop {
+            let event = match replayed.next() {
+                Some(event) => event,
+                None => match receiver.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+            };
+
+            let data = match serde_json::to_string(&event) {
+                Ok(data) => data,
+                Err(e) => {
+                    error!("Failed to serialize overlay event: {:#?}", e);
+                    continue;
+                }
+            };
+
+            if sender
+                .send_data(format!("event: {}\ndata: {data}\n\n", event.name()).into())
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    response("text/event-stream", body)
+}
+
+/// Serves the file with the same range and conditional request handling as the `data://`
+/// protocol. The body is streamed, so big videos are never read whole
+fn file_response(headers: &HeaderMap, file_path: PathBuf) -> Response<Body> {
+    let (file, metadata) = match File::open(&file_path).and_then(|file| {
+        let metadata = file.metadata()?;
+        Ok((file, metadata))
+    }) {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Failed to read {}: {:#?}", file_path.display(), e);
+            return status_response(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let size = metadata.len();
+    let modified = metadata.modified().ok();
+    let etag = entity_tag(&metadata);
+
+    let mut builder = Response::builder()
+        .header(
+            CONTENT_TYPE,
+            mime_guess::from_path(&file_path)
+                .first_or_octet_stream()
+                .essence_str(),
+        )
+        .header(ACCEPT_RANGES, "bytes")
+        .header(CACHE_CONTROL, "no-cache")
+        .header(ETAG, etag.as_str());
+    if let Some(modified) = modified {
+        builder = builder.header(LAST_MODIFIED, httpdate::fmt_http_date(modified));
+    }
+
+    if is_not_modified(headers, &etag, modified) {
+        return build(builder.status(StatusCode::NOT_MODIFIED), Body::empty());
+    }
+
+    let (builder, start, length) = match requested_range(headers, &etag, modified, size) {
+        Ok(Some(range)) => (
+            builder.status(StatusCode::PARTIAL_CONTENT).header(
+                CONTENT_RANGE,
+                format!(
+                    "bytes {}-{}/{size}",
+                    range.start,
+                    range.start + range.length - 1
+                ),
+            ),
+            range.start,
+            range.length,
+        ),
+        Ok(None) => (builder, 0, size),
+        Err(()) => {
+            return build(
+                builder
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(CONTENT_RANGE, format!("bytes */{size}")),
+                Body::empty(),
+            )
+        }
+    };
+
+    let (sender, body) = Body::channel();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = send_file(tokio::fs::File::from_std(file), start, length, sender).await {
+            error!("Failed to send {}: {:#?}", file_path.display(), e);
+        }
+    });
+
+    build(builder.header(CONTENT_LENGTH, length.to_string()), body)
+}
+
+/// Sends `length` bytes of the file from `start` piece by piece
+async fn send_file(
+    mut file: tokio::fs::File,
+    start: u64,
+    length: u64,
+    mut sender: Sender,
+) -> anyhow::Result<()> {
+    file.seek(SeekFrom::Start(start)).await?;
+    let mut file = file.take(length);
+    loop {
+        let mut chunk = vec![0; FILE_CHUNK_SIZE];
+        let read = file.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        chunk.truncate(read);
+        // The client is gone
+        if sender.send_data(chunk.into()).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+fn response(content_type: &str, body: Body) -> Response<Body> {
+    build(
+        Response::builder()
+            .header(CONTENT_TYPE, content_type)
+            .header(CACHE_CONTROL, "no-cache"),
+        body,
+    )
+}
+
+fn build(builder: Builder, body: Body) -> Response<Body> {
+    builder
+        .body(body)
+        .unwrap_or_else(|_| status_response(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
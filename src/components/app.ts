@@ -9,9 +9,13 @@ import { Event as TauriEvent, listen, UnlistenFn } from "@tauri-apps/api/event";
 import { AppActions, AppState, Config, WheelState } from "../types";
 import Settings from "./settings";
 import Wheel from "./wheel";
-import errorToast from "../func";
+import errorToast, { setAudioLevel, warningToast } from "../func";
 
 const EMPTY_NAME = "-----";
+// Shortest time between activity reports to the attract mode, ms
+const WAKE_INTERVAL = 1000;
+const VIDEO_EXTENSIONS = ["mp4", "webm"];
+const WAKE_EVENTS = ["keydown", "mousedown", "mousemove", "touchstart", "wheel"];
 
 export default class App {
   private actions: AppActions;
@@ -26,6 +30,8 @@ export default class App {
 
   private isRefreshingLists: boolean = false;
 
+  private lastWake: number = 0;
+
   private state: AppState;
 
   private unlistens: UnlistenFn[] = [];
@@ -43,10 +49,35 @@ export default class App {
   constructor() {
     this.actions = this.appActions();
     this.state = {
+      audioError: null,
       config: {
+        attractDelay: 0,
+        audioCacheSize: 256,
         audioDevice: "",
+        motionMode: "random",
         music: true,
+        musicDuckVolume: 30,
+        musicFadeOut: 1000,
+        musicFollowSpeed: false,
+        musicRandomStart: false,
+        musicSpeedMax: 1.0,
+        musicSpeedMin: 0.5,
+        musicTransition: "sequential",
+        musicVolume: 100,
+        normalizeLoudness: false,
+        oscEnabled: false,
+        oscListenAddress: "127.0.0.1:9000",
+        oscSendAddress: "",
+        overlayBackgroundColor: "transparent",
+        overlayEnabled: false,
+        overlayFontSize: 48,
+        overlayPort: 8790,
+        overlayShowBackground: false,
+        overlayTextColor: "#ffffff",
+        overlayWinnerColor: "#ffdd57",
         reverseChance: 0.25,
+        soundRotation: "shuffleBag",
+        spectrum: false,
         speedReduceMax: 0.05,
         speedReduceMin: 0.03,
         speedSlowLimit: 1.0,
@@ -57,6 +88,12 @@ export default class App {
         speedStopMax: 0.5,
         speedStopMin: 0.25,
         startFullscreen: false,
+        stingerVolume: 100,
+        stopSoundFadeIn: 0,
+        tickMinInterval: 30,
+        tickPitch: false,
+        tickVolume: 100,
+        ticks: true,
         volume: 100,
       },
       settingsActive: false,
@@ -82,6 +119,10 @@ export default class App {
       this.onKeyDown(e)
     );
 
+    WAKE_EVENTS.forEach((name) =>
+      document.addEventListener(name, () => this.wake(false))
+    );
+
     this.unlistens.push(
       await listen("wheel-list", (event: TauriEvent<string[]>) => {
         this.wheelItems = event.payload;
@@ -89,19 +130,81 @@ export default class App {
       })
     );
 
+    this.unlistens.push(
+      await listen("roll", () => {
+        this.wheelState = WheelState.Rolling;
+        this.actions.setSettingsActive(false);
+        m.redraw();
+      })
+    );
+
+    this.unlistens.push(
+      await listen("stopping", () => {
+        this.wheelState = WheelState.Stopping;
+        m.redraw();
+      })
+    );
+
     this.unlistens.push(
       await listen("stop", () => {
         this.wheelState = WheelState.Stopped;
+        setAudioLevel(0);
         m.redraw();
       })
     );
 
+    this.unlistens.push(
+      await listen("spectrum", (event: TauriEvent<number[]>) => {
+        // Bass bands drive the pulse, the style reads the level without a redraw
+        const bass = event.payload.slice(0, 4);
+        setAudioLevel(Math.max(0, ...bass));
+      })
+    );
+
+    this.unlistens.push(
+      await listen(
+        "audio-devices-changed",
+        (event: TauriEvent<[string, string][]>) => {
+          this.audioDevices = event.payload;
+          m.redraw();
+        }
+      )
+    );
+
+    this.unlistens.push(
+      await listen("background", (event: TauriEvent<string | null>) => {
+        // The only way backgrounds arrive: requested by the list selection, or picked by the
+        // attract mode, a slideshow or the winner
+        if (event.payload === null) {
+          this.setBackground(null);
+          return;
+        }
+
+        const extension = event.payload.split(".").pop()?.toLowerCase();
+        const type = VIDEO_EXTENSIONS.includes(extension ?? "")
+          ? `video/${extension}`
+          : "image/";
+        this.setBackground([event.payload, type]);
+      })
+    );
+
+    this.unlistens.push(
+      await listen("audio-error", (event: TauriEvent<string | null>) => {
+        this.setAudioError(event.payload);
+      })
+    );
+
+    invoke<string | null>("get_audio_error")
+      .then((error: string | null) => this.setAudioError(error))
+      .catch((error) => errorToast(error));
+
     await this.refreshLists();
     if (this.lists.length > 0) {
       [this.currentList] = this.lists;
     }
 
     this.getBackground();
+    this.wake(true);
   }
 
   view() {
@@ -161,31 +264,48 @@ export default class App {
   }
 
   private getBackground() {
-    invoke<[string, string]>("random_bg", { listName: this.currentList })
-      .then((data: [string, string]) => {
-        if (data === null) {
-          this.background = null;
-        } else if (data[1].startsWith("image/")) {
-          this.background = m("img#bg", {
+    // The background arrives with the "background" event
+    invoke("random_bg", { listName: this.currentList }).catch((error) =>
+      errorToast(error)
+    );
+  }
+
+  private setBackground(data: [string, string] | null) {
+    if (data === null) {
+      this.background = null;
+    } else if (data[1].startsWith("image/")) {
+      // Big images are scaled down to the screen by the backend, decoding them is slow
+      const width = Math.ceil(window.screen.width * window.devicePixelRatio);
+      this.background = m("img#bg", {
+        src: `${convertFileSrc(data[0], "data")}?w=${width}`,
+      });
+    } else if (data[1].startsWith("video/")) {
+      this.background = m(
+        "video#bg[autoplay][loop][muted]",
+        {
+          onupdate: ({ dom }) => (dom as HTMLVideoElement).load(),
+        },
+        [
+          m("source", {
             src: convertFileSrc(data[0], "data"),
-          });
-        } else if (data[1].startsWith("video/")) {
-          this.background = m(
-            "video#bg[autoplay][loop][muted]",
-            {
-              onupdate: ({ dom }) => (dom as HTMLVideoElement).load(),
-            },
-            [
-              m("source", {
-                src: convertFileSrc(data[0], "data"),
-                type: data[1],
-              }),
-            ]
-          );
-        }
-        m.redraw();
-      })
-      .catch((error) => errorToast(error));
+            type: data[1],
+          }),
+        ]
+      );
+    }
+    m.redraw();
+  }
+
+  private wake(force: boolean) {
+    const now = Date.now();
+    if (!force && now - this.lastWake < WAKE_INTERVAL) {
+      return;
+    }
+
+    this.lastWake = now;
+    invoke("wake", { listName: this.currentList }).catch((error) =>
+      errorToast(error)
+    );
   }
 
   private appActions(): AppActions {
@@ -239,6 +359,7 @@ export default class App {
   private onListChanged(event: Event) {
     this.currentList = (event.target as HTMLSelectElement).value;
     this.getBackground();
+    this.wake(true);
   }
 
   private onRollClicked() {
@@ -270,6 +391,14 @@ export default class App {
     this.toggleListRefreshing(false);
   }
 
+  private setAudioError(error: string | null) {
+    if (error !== null) {
+      warningToast(`Audio is disabled: ${error}`);
+    }
+    this.state.audioError = error;
+    m.redraw();
+  }
+
   private toggleListRefreshing(value: boolean) {
     this.isRefreshingLists = value;
     m.redraw();
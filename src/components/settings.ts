@@ -26,6 +26,20 @@ function formField(
   ]);
 }
 
+function volumeSlider(label: string, name: string, value: number): m.Children {
+  return formField(
+    label,
+    "is-normal",
+    formControl(
+      m(
+        `input#${name}.slider.is-fullwidth.has-output[name=${name}][type=range][min=0][max=100][step=1]`,
+        { value }
+      ),
+      m("output", { for: name }, value)
+    )
+  );
+}
+
 function randomRange(
   label: string,
   minName: string,
@@ -93,6 +107,12 @@ export default class Settings {
             onchange: (e: Event) => this.onFormFieldChanged(e),
           },
           [
+            this.state.audioError === null
+              ? null
+              : m(
+                  ".notification.is-warning",
+                  `Audio is disabled: ${this.state.audioError}. Choose another device and save settings to try again.`
+                ),
             formField(
               "Start in full screen",
               "",
@@ -111,6 +131,120 @@ export default class Settings {
                 })
               )
             ),
+            formField(
+              "Normalize loudness",
+              "",
+              formControl(
+                m("input[name=normalizeLoudness][type=checkbox]", {
+                  checked: this.state.config.normalizeLoudness,
+                })
+              )
+            ),
+            formField(
+              "Start music at a random position",
+              "",
+              formControl(
+                m("input[name=musicRandomStart][type=checkbox]", {
+                  checked: this.state.config.musicRandomStart,
+                })
+              )
+            ),
+            formField(
+              "Sound order",
+              "",
+              formControl(
+                m(
+                  ".select",
+                  m(
+                    "select.is-fullwidth[name=soundRotation]",
+                    [
+                      ["random", "Random"],
+                      ["shuffleBag", "Shuffle, no repeats"],
+                      ["leastRecentlyPlayed", "Least recently played"],
+                    ].map(([value, label]) =>
+                      m(
+                        "option",
+                        {
+                          selected: value === this.state.config.soundRotation,
+                          value,
+                        },
+                        label
+                      )
+                    )
+                  )
+                )
+              )
+            ),
+            formField(
+              "Music follows wheel speed",
+              "",
+              formControl(
+                m("input[name=musicFollowSpeed][type=checkbox]", {
+                  checked: this.state.config.musicFollowSpeed,
+                })
+              )
+            ),
+            formField(
+              "Pulse background with music",
+              "",
+              formControl(
+                m("input[name=spectrum][type=checkbox]", {
+                  checked: this.state.config.spectrum,
+                })
+              )
+            ),
+            randomRange(
+              "Music speed",
+              "musicSpeedMin",
+              "musicSpeedMax",
+              this.state.config.musicSpeedMin,
+              this.state.config.musicSpeedMax
+            ),
+            formField(
+              "Ticks",
+              "",
+              formControl(
+                m("input[name=ticks][type=checkbox]", {
+                  checked: this.state.config.ticks,
+                })
+              )
+            ),
+            formField(
+              "Raise tick pitch with speed",
+              "",
+              formControl(
+                m("input[name=tickPitch][type=checkbox]", {
+                  checked: this.state.config.tickPitch,
+                })
+              )
+            ),
+            formField(
+              "Minimum time between ticks, ms",
+              "is-normal",
+              formControl(
+                m("input.input[name=tickMinInterval][type=number][min=1]", {
+                  value: this.state.config.tickMinInterval,
+                })
+              )
+            ),
+            formField(
+              "Audio cache, MB",
+              "is-normal",
+              formControl(
+                m("input.input[name=audioCacheSize][type=number][min=0]", {
+                  value: this.state.config.audioCacheSize,
+                })
+              )
+            ),
+            formField(
+              "Attract mode after idle, s (0 = off)",
+              "is-normal",
+              formControl(
+                m("input.input[name=attractDelay][type=number][min=0]", {
+                  value: this.state.config.attractDelay,
+                })
+              )
+            ),
             formField(
               "Audio device",
               "",
@@ -146,6 +280,103 @@ export default class Settings {
                 m("output", { for: "volume" }, this.state.config.volume)
               )
             ),
+            volumeSlider(
+              "Music volume",
+              "musicVolume",
+              this.state.config.musicVolume
+            ),
+            volumeSlider(
+              "Stop sound volume",
+              "stingerVolume",
+              this.state.config.stingerVolume
+            ),
+            volumeSlider(
+              "Tick volume",
+              "tickVolume",
+              this.state.config.tickVolume
+            ),
+            formField(
+              "Music at stop",
+              "",
+              formControl(
+                m(
+                  ".select",
+                  m(
+                    "select.is-fullwidth[name=musicTransition]",
+                    [
+                      ["sequential", "Fade out, then stop sound"],
+                      ["crossfade", "Crossfade with stop sound"],
+                      ["duck", "Duck under stop sound"],
+                    ].map(([value, label]) =>
+                      m(
+                        "option",
+                        {
+                          selected:
+                            value === this.state.config.musicTransition,
+                          value,
+                        },
+                        label
+                      )
+                    )
+                  )
+                )
+              )
+            ),
+            formField(
+              "Music fade-out, ms",
+              "is-normal",
+              formControl(
+                m("input.input[name=musicFadeOut][type=number][min=0]", {
+                  value: this.state.config.musicFadeOut,
+                })
+              )
+            ),
+            formField(
+              "Stop sound fade-in, ms",
+              "is-normal",
+              formControl(
+                m("input.input[name=stopSoundFadeIn][type=number][min=0]", {
+                  value: this.state.config.stopSoundFadeIn,
+                })
+              )
+            ),
+            formField(
+              "Ducked music volume",
+              "is-normal",
+              formControl(
+                m(
+                  "input.input[name=musicDuckVolume][type=number][min=0][max=100]",
+                  {
+                    value: this.state.config.musicDuckVolume,
+                  }
+                )
+              )
+            ),
+            formField(
+              "Wheel motion",
+              "",
+              formControl(
+                m(
+                  ".select",
+                  m(
+                    "select.is-fullwidth[name=motionMode]",
+                    [
+                      ["random", "Random slowdown"],
+                      ["musicCue", "Stop at music cue"],
+                    ].map(([value, label]) =>
+                      m(
+                        "option",
+                        {
+                          selected: value === this.state.config.motionMode,
+                          value,
+                        },
+                        label
+                      )
+                    )
+                  )
+                )
+              )
+            ),
             formField(
               "Reverse roll chance",
               "is-normal",
@@ -200,6 +431,101 @@ export default class Settings {
               this.state.config.speedStopMin,
               this.state.config.speedStopMax
             ),
+            formField(
+              "OSC control",
+              "",
+              formControl(
+                m("input[name=oscEnabled][type=checkbox]", {
+                  checked: this.state.config.oscEnabled,
+                })
+              )
+            ),
+            formField(
+              "OSC listen address",
+              "is-normal",
+              formControl(
+                m("input.input[name=oscListenAddress][type=text]", {
+                  placeholder: "127.0.0.1:9000",
+                  value: this.state.config.oscListenAddress,
+                })
+              )
+            ),
+            formField(
+              "OSC feedback address",
+              "is-normal",
+              formControl(
+                m("input.input[name=oscSendAddress][type=text]", {
+                  placeholder: "192.168.1.10:9001",
+                  value: this.state.config.oscSendAddress,
+                })
+              )
+            ),
+            formField(
+              "OBS overlay",
+              "",
+              formControl(
+                m("input[name=overlayEnabled][type=checkbox]", {
+                  checked: this.state.config.overlayEnabled,
+                })
+              )
+            ),
+            formField(
+              "Overlay port",
+              "is-normal",
+              formControl(
+                m(
+                  "input.input[name=overlayPort][type=number][min=1][max=65535]",
+                  {
+                    value: this.state.config.overlayPort,
+                  }
+                )
+              )
+            ),
+            formField(
+              "Overlay background",
+              "",
+              formControl(
+                m("input[name=overlayShowBackground][type=checkbox]", {
+                  checked: this.state.config.overlayShowBackground,
+                })
+              )
+            ),
+            formField(
+              "Overlay background color",
+              "is-normal",
+              formControl(
+                m("input.input[name=overlayBackgroundColor][type=text]", {
+                  value: this.state.config.overlayBackgroundColor,
+                })
+              )
+            ),
+            formField(
+              "Overlay text color",
+              "is-normal",
+              formControl(
+                m("input.input[name=overlayTextColor][type=text]", {
+                  value: this.state.config.overlayTextColor,
+                })
+              )
+            ),
+            formField(
+              "Overlay winner color",
+              "is-normal",
+              formControl(
+                m("input.input[name=overlayWinnerColor][type=text]", {
+                  value: this.state.config.overlayWinnerColor,
+                })
+              )
+            ),
+            formField(
+              "Overlay font size",
+              "is-normal",
+              formControl(
+                m("input.input[name=overlayFontSize][type=number][min=1]", {
+                  value: this.state.config.overlayFontSize,
+                })
+              )
+            ),
           ]
         ),
         m("footer.modal-card-foot", [
@@ -7,3 +7,15 @@ export default function errorToast(message: string) {
     type: "is-danger",
   });
 }
+
+export function warningToast(message: string) {
+  bulmaToast.toast({
+    message,
+    duration: 5000,
+    type: "is-warning",
+  });
+}
+
+export function setAudioLevel(level: number) {
+  document.documentElement.style.setProperty("--audio-level", `${level}`);
+}
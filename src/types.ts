@@ -4,19 +4,50 @@ export enum WheelState {
   Stopping,
 }
 
+export type MotionMode = "random" | "musicCue";
+
+export type MusicTransition = "sequential" | "crossfade" | "duck";
+
+export type SoundRotation = "random" | "shuffleBag" | "leastRecentlyPlayed";
+
 export interface AppActions {
   setSettingsActive(value: boolean): void;
 }
 
 export interface AppState {
+  audioError: string | null;
   config: Config;
   settingsActive: boolean;
 }
 
 export interface Config {
+  attractDelay: number;
+  audioCacheSize: number;
   audioDevice: string;
+  motionMode: MotionMode;
   music: boolean;
+  musicDuckVolume: number;
+  musicFadeOut: number;
+  musicFollowSpeed: boolean;
+  musicRandomStart: boolean;
+  musicSpeedMax: number;
+  musicSpeedMin: number;
+  musicTransition: MusicTransition;
+  musicVolume: number;
+  normalizeLoudness: boolean;
+  oscEnabled: boolean;
+  oscListenAddress: string;
+  oscSendAddress: string;
+  overlayBackgroundColor: string;
+  overlayEnabled: boolean;
+  overlayFontSize: number;
+  overlayPort: number;
+  overlayShowBackground: boolean;
+  overlayTextColor: string;
+  overlayWinnerColor: string;
   reverseChance: number;
+  soundRotation: SoundRotation;
+  spectrum: boolean;
   speedReduceMax: number;
   speedReduceMin: number;
   speedSlowLimit: number;
@@ -27,6 +58,12 @@ export interface Config {
   speedStopMax: number;
   speedStopMin: number;
   startFullscreen: boolean;
+  stingerVolume: number;
+  stopSoundFadeIn: number;
+  tickMinInterval: number;
+  tickPitch: boolean;
+  tickVolume: number;
+  ticks: boolean;
   volume: number;
   [key: string]: string | number | boolean;
 }